@@ -0,0 +1,78 @@
+//! Removal of the partial file of a download
+//! when the client is interrupted (Ctrl-C).
+
+use std::path::Path;
+
+#[cfg(unix)]
+use std::{
+    ffi::CString,
+    os::unix::ffi::OsStrExt,
+    ptr,
+    sync::{
+        atomic::{AtomicPtr, Ordering},
+        Once,
+    },
+};
+
+/// Path of the partial file, read by the signal handler.
+#[cfg(unix)]
+static PARTIAL_FILE: AtomicPtr<libc::c_char> = AtomicPtr::new(ptr::null_mut());
+
+#[cfg(unix)]
+static HANDLER: Once = Once::new();
+
+/// Removes the file at `path` if the client is
+/// interrupted (SIGINT, SIGTERM or SIGHUP).
+#[cfg(unix)]
+pub fn remove_on_interrupt(path: &Path) {
+    HANDLER.call_once(|| {
+        for signal in [libc::SIGINT, libc::SIGTERM, libc::SIGHUP] {
+            // SAFETY: the handler only calls
+            // async-signal-safe functions
+            unsafe {
+                libc::signal(signal, on_interrupt as *const () as libc::sighandler_t);
+            }
+        }
+    });
+
+    if let Ok(path) = CString::new(path.as_os_str().as_bytes()) {
+        free(PARTIAL_FILE.swap(path.into_raw(), Ordering::SeqCst));
+    }
+}
+
+/// Keeps the partial file, if any, when the client is interrupted.
+#[cfg(unix)]
+pub fn keep_on_interrupt() {
+    free(PARTIAL_FILE.swap(ptr::null_mut(), Ordering::SeqCst));
+}
+
+#[cfg(unix)]
+fn free(path: *mut libc::c_char) {
+    if !path.is_null() {
+        // SAFETY: `path` comes from `CString::into_raw`
+        // and is no longer reachable by the handler
+        drop(unsafe { CString::from_raw(path) });
+    }
+}
+
+#[cfg(unix)]
+extern "C" fn on_interrupt(signal: libc::c_int) {
+    let path = PARTIAL_FILE.load(Ordering::SeqCst);
+
+    // SAFETY: `unlink` and `_exit` are async-signal-safe,
+    // and `path` is a valid C string when not null
+    unsafe {
+        if !path.is_null() {
+            libc::unlink(path);
+        }
+
+        libc::_exit(128 + signal);
+    }
+}
+
+/// Interrupts are not handled on this platform.
+#[cfg(not(unix))]
+pub fn remove_on_interrupt(_path: &Path) {}
+
+#[cfg(not(unix))]
+pub fn keep_on_interrupt() {}
@@ -0,0 +1,118 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io,
+    path::{Path, PathBuf},
+    process,
+};
+
+mod interrupt;
+
+/// Policy applied when the destination of a download already exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverwritePolicy {
+    NoClobber,
+    Overwrite,
+    Backup,
+}
+
+/// Temporary file receiving a download in the directory of its
+/// destination, so that the destination is only replaced, by an
+/// atomic rename, once the whole file has been received.
+///
+/// The temporary file is removed when the download is dropped
+/// before completion, and when the client is interrupted.
+#[derive(Debug)]
+pub struct PartialDownload {
+    file: File,
+    temp_path: PathBuf,
+    destination: PathBuf,
+    overwrite_policy: OverwritePolicy,
+    completed: bool,
+}
+
+impl PartialDownload {
+    /// Creates the temporary file of a download into `destination`.
+    ///
+    /// With `NoClobber`, an existing destination is refused up front.
+    pub fn create(destination: &Path, overwrite_policy: OverwritePolicy) -> io::Result<Self> {
+        if overwrite_policy == OverwritePolicy::NoClobber && destination.exists() {
+            return Err(io::Error::from(io::ErrorKind::AlreadyExists));
+        }
+
+        let file_name = destination
+            .file_name()
+            .ok_or(io::Error::from(io::ErrorKind::InvalidInput))?;
+
+        let temp_path = destination.with_file_name(format!(
+            ".{}.{}.part",
+            file_name.to_string_lossy(),
+            process::id()
+        ));
+
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temp_path)?;
+
+        interrupt::remove_on_interrupt(&temp_path);
+
+        Ok(Self {
+            file,
+            temp_path,
+            destination: destination.to_owned(),
+            overwrite_policy,
+            completed: false,
+        })
+    }
+
+    /// Returns the temporary file receiving the data.
+    pub fn file(&mut self) -> &mut File {
+        &mut self.file
+    }
+
+    /// Moves the received file to its destination.
+    ///
+    /// An existing destination is refused (`NoClobber`), replaced
+    /// (`Overwrite`), or renamed with a trailing `~` (`Backup`).
+    pub fn complete(mut self) -> io::Result<()> {
+        self.file.sync_all()?;
+
+        match self.overwrite_policy {
+            // A hard link is never created over an existing file,
+            // unlike a rename, which replaces it
+            OverwritePolicy::NoClobber => match fs::hard_link(&self.temp_path, &self.destination) {
+                Ok(()) => fs::remove_file(&self.temp_path)?,
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => return Err(e),
+                // Filesystems without hard links
+                Err(_) if self.destination.exists() => {
+                    return Err(io::Error::from(io::ErrorKind::AlreadyExists))
+                }
+                Err(_) => fs::rename(&self.temp_path, &self.destination)?,
+            },
+            OverwritePolicy::Overwrite => fs::rename(&self.temp_path, &self.destination)?,
+            OverwritePolicy::Backup => {
+                if self.destination.exists() {
+                    let mut backup_path = self.destination.clone().into_os_string();
+                    backup_path.push("~");
+
+                    fs::rename(&self.destination, backup_path)?;
+                }
+
+                fs::rename(&self.temp_path, &self.destination)?;
+            }
+        }
+
+        self.completed = true;
+        Ok(())
+    }
+}
+
+impl Drop for PartialDownload {
+    fn drop(&mut self) {
+        interrupt::keep_on_interrupt();
+
+        if !self.completed {
+            let _ = fs::remove_file(&self.temp_path);
+        }
+    }
+}
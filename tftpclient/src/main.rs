@@ -1,282 +1,602 @@
 use std::{
     env,
-    fs::{File, OpenOptions},
-    io::{Read, Write},
-    net::UdpSocket,
+    fs::File,
+    io::{self, BufWriter, Read, Seek, Write},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket},
     path::Path,
+    time::Duration,
 };
 
-use rand::Rng;
-use tftppacket::{ACKPacket, DATAPacket, ERRORPacket, RRQPacket, TFTPPacket, WRQPacket};
-use utils::{ClientAction, ClientArgs};
+use download::PartialDownload;
+use tftppacket::{
+    netascii_size, ACKPacket, DATAPacket, ERRORPacket, NetasciiReader, NetasciiWriter, OACKPacket,
+    PacketRef, RRQPacket, TFTPOptions, TransferMode, WRQPacket,
+};
+use tftptransfer::{
+    acknowledge_file, receive_file, send_file, too_large_error, RttEstimator, TransferOptions,
+    TransferSocket,
+};
+use transfer::{
+    abort_transfer, file_error_packet, send_request, server_error, transfer_error, warn_unknown_tid,
+};
+use utils::{ClientAction, ClientArgs, OutputFormat, Verbosity};
 
+mod download;
+mod transfer;
 mod utils;
 
 fn main() -> Result<(), String> {
     let client_args = ClientArgs::build()?;
 
-    let client_socket = loop {
-        let client_tid: u16 = rand::thread_rng().gen_range(0..=65535);
+    // Options requested to the server (RFC 2347)
+    let mut requested_options = TFTPOptions::new();
 
-        match UdpSocket::bind(format!("0.0.0.0:{}", client_tid)) {
-            Ok(socket) => break socket,
-            Err(_) => continue,
-        };
-    };
+    if let Some(block_size) = client_args.block_size {
+        requested_options.insert("blksize", &block_size.to_string());
+    }
 
-    match client_args.action {
-        ClientAction::Read => {
-            let rrq = RRQPacket::create_rrq_packet(&client_args.filename, "octet");
+    if let Some(window_size) = client_args.window_size {
+        requested_options.insert("windowsize", &window_size.to_string());
+    }
 
-            client_socket
-                .send_to(&rrq, format!("{}:69", client_args.remote_ip))
-                .map_err(|e| format!("Unable to initialize a RRQ packet to the server: {}", e))?;
+    if let Some(timeout) = client_args.timeout {
+        requested_options.insert("timeout", &timeout.to_string());
+    }
 
-            // Create a buffer to store the first TFTP DATA packet
-            // - Opcode: 2 bytes
-            // - Block number: 2 bytes
-            // - Data: 512 bytes
-            let mut response = [0_u8; 516];
+    // Until an OACK is received, the
+    // transfer follows RFC 1350
+    let mut transfer_options = TransferOptions {
+        timeout: client_args
+            .timeout
+            .map(Duration::from_secs)
+            .unwrap_or(TransferOptions::DEFAULT_TIMEOUT),
+        retries: client_args.retries,
+        rollover: client_args.rollover,
+        ..Default::default()
+    };
 
-            let (recv_packet_len, server_addr) = client_socket
-                .recv_from(&mut response)
-                .map_err(|e| format!("Unable to receive a DATA packet from the server: {}", e))?;
-
-            // Ensure connection with the server
-            client_socket
-                .connect(server_addr)
-                .map_err(|e| format!("Unable to etablish a connection with the server: {}", e))?;
-
-            let first_data_packet = match TFTPPacket::parse(&response[..recv_packet_len]) {
-                Ok(TFTPPacket::DATA(packet)) => packet,
-                Ok(TFTPPacket::ERROR(err_packet)) => {
-                    return Err(format!(
-                        "File transmission aborted due to an error: {}",
-                        err_packet.get_error_message()
-                    ));
-                }
-                _ => {
-                    let err_packet = ERRORPacket::IllegalTftpOperation;
-                    client_socket.send(&err_packet.as_bytes());
-                    return Err(format!(
-                        "File transmission aborted due to an error: {}",
-                        err_packet.get_error_message()
-                    ));
-                }
-            };
+    let mut rtt_estimator = RttEstimator::new(&transfer_options);
 
-            if first_data_packet.block != 1 {
-                let err_packet = ERRORPacket::IllegalTftpOperation;
-                client_socket.send(&err_packet.as_bytes());
-                return Err(format!(
-                    "File transmission aborted due to an error: {}",
-                    err_packet.get_error_message()
-                ));
+    match client_args.action {
+        ClientAction::Read => {
+            // The server reports the size
+            // of the file in the OACK (RFC 2349)
+            if client_args.transfer_size {
+                requested_options.insert("tsize", "0");
             }
 
             let working_dir = env::current_dir()
                 .map_err(|e| format!("File transmission aborted due to an error: {}", e))?;
 
-            let filename = Path::new(&client_args.filename).file_name().unwrap();
-
-            let mut file = OpenOptions::new()
-                .create_new(true)
-                .append(true)
-                .open(working_dir.join(filename))
-                .map_err(|e| format!("File transmission aborted due to an error: {}", e))?;
+            let local_path = working_dir.join(&client_args.local_filename);
 
-            let write_bytes = file
-                .write(first_data_packet.get_data())
-                .map_err(|e| format!("File transmission aborted due to an error: {}", e))?;
+            let local_file_error = |e: io::Error| match e.kind() {
+                io::ErrorKind::AlreadyExists => format!(
+                    "{} already exists (see --overwrite and --backup)",
+                    local_path.display()
+                ),
+                _ => format!("Unable to store the downloaded file: {}", e),
+            };
 
-            let ack = ACKPacket {
-                block: first_data_packet.block,
+            // The data is received into a temporary file, which
+            // replaces the destination once the download succeeds.
+            // It is created before the request, so that an existing
+            // destination is refused before the server sends any data
+            let mut partial_download = match client_args.uses_stdio() {
+                true => None,
+                false => Some(
+                    PartialDownload::create(&local_path, client_args.overwrite_policy)
+                        .map_err(local_file_error)?,
+                ),
             };
 
-            client_socket
-                .send(&ack.as_bytes())
-                .map_err(|e| format!("File transmission aborted due to an error: {}", e))?;
+            let rrq = RRQPacket::create_rrq_packet(
+                &client_args.remote_filename,
+                client_args.mode,
+                &requested_options,
+            );
 
-            if write_bytes < 512 {
-                println!("Download completed!");
-                println!("File path: {}", working_dir.join(filename).display());
-                return Ok(());
-            }
-
-            let mut last_block_number = first_data_packet.block;
+            // Create a buffer to store the first response
+            // which is either an OACK or a TFTP DATA packet
+            // - Opcode: 2 bytes
+            // - Block number: 2 bytes
+            // - Data: up to the requested block size
+            // One more byte reveals an oversized DATA packet
+            let mut response = vec![
+                0_u8;
+                client_args
+                    .block_size
+                    .unwrap_or(DATAPacket::DEFAULT_BLOCK_SIZE)
+                    .max(DATAPacket::DEFAULT_BLOCK_SIZE)
+                    + 5
+            ];
+
+            let (client_socket, recv_packet_len, server_tid) = request_server(
+                &client_args,
+                &rrq,
+                &mut response,
+                &transfer_options,
+                &mut rtt_estimator,
+            )?;
+
+            // Packets from any other source than
+            // the server are rejected (RFC 1350)
+            let client_socket = TransferSocket::new(client_socket, server_tid, warn_unknown_tid);
+
+            let pending_data_packet = match PacketRef::parse(&response[..recv_packet_len]) {
+                Ok(PacketRef::OACK(oack_packet)) => {
+                    apply_oack(
+                        &client_socket,
+                        &OACKPacket::from(&oack_packet),
+                        &requested_options,
+                        &mut transfer_options,
+                    )?;
+
+                    if let Some(transfer_size) = transfer_options.transfer_size {
+                        if client_args.prints_status(Verbosity::Normal) {
+                            eprintln!("File size: {} bytes", transfer_size);
+                        }
+                    }
 
-            loop {
-                // Create a buffer to store the next
-                // TFTP DATA packet (516 bytes)
-                let mut response = [0_u8; 516];
+                    // Acknowledge the OACK with the block number 0
+                    let ack = ACKPacket { block: 0 };
 
-                let recv_packet_len = client_socket
-                    .recv(&mut response)
-                    .map_err(|e| format!("File transmission aborted due to an error: {}", e))?;
+                    client_socket
+                        .send(&ack.as_bytes())
+                        .map_err(|e| format!("File transmission aborted due to an error: {}", e))?;
 
-                let data_packet = match TFTPPacket::parse(&response[..recv_packet_len]) {
-                    Ok(TFTPPacket::DATA(packet)) => packet,
-                    Ok(TFTPPacket::ERROR(err_packet)) => {
-                        return Err(format!(
-                            "File transmission aborted due to an error: {}",
-                            err_packet.get_error_message()
-                        ));
-                    }
-                    _ => {
-                        let err_packet = ERRORPacket::IllegalTftpOperation;
-                        client_socket.send(&err_packet.as_bytes());
-                        return Err(format!(
-                            "File transmission aborted due to an error: {}",
-                            err_packet.get_error_message()
-                        ));
-                    }
-                };
-
-                if data_packet.block != (last_block_number + 1) {
-                    let err_packet = ERRORPacket::IllegalTftpOperation;
-                    client_socket.send(&err_packet.as_bytes());
-                    return Err(format!(
-                        "File transmission aborted due to an error: {}",
-                        err_packet.get_error_message()
+                    None
+                }
+                // The server ignored the options
+                Ok(PacketRef::DATA(packet)) => Some(DATAPacket::from(&packet)),
+                Ok(PacketRef::ERROR(err_packet)) => {
+                    return Err(server_error(
+                        err_packet.get_error_code(),
+                        err_packet.get_error_message(),
                     ));
                 }
+                _ => {
+                    return Err(abort_transfer(
+                        &client_socket,
+                        ERRORPacket::IllegalTftpOperation,
+                    ))
+                }
+            };
 
-                let write_bytes = file
-                    .write(data_packet.get_data())
-                    .map_err(|e| format!("File transmission aborted due to an error: {}", e))?;
+            // Refuse the download before any data is received
+            // when the file cannot be numbered without rollover
+            if transfer_options
+                .transfer_size
+                .is_some_and(|transfer_size| transfer_options.exceeds_block_numbers(transfer_size))
+            {
+                return Err(abort_transfer(&client_socket, too_large_error()));
+            }
 
-                let ack = ACKPacket {
-                    block: data_packet.block,
-                };
+            // A negotiated timeout replaces the adaptive one
+            if !transfer_options.adaptive_timeout {
+                rtt_estimator = RttEstimator::new(&transfer_options);
+            }
 
-                client_socket
-                    .send(&ack.as_bytes())
-                    .map_err(|e| format!("File transmission aborted due to an error: {}", e))?;
+            let mut writer: Box<dyn Write> = match partial_download.as_mut() {
+                Some(partial_download) => Box::new(partial_download.file()),
+                None => Box::new(BufWriter::new(io::stdout().lock())),
+            };
 
-                if write_bytes < 512 {
-                    println!("Download completed!");
-                    println!("File path: {}", working_dir.join(filename).display());
-                    return Ok(());
+            print_transfer_options(&client_args, server_tid, &transfer_options);
+
+            let received_file = match client_args.mode {
+                // The last CR of netascii data is only
+                // written once the whole file is received
+                TransferMode::Netascii => {
+                    let mut netascii_writer = NetasciiWriter::new(writer);
+
+                    let received_file = receive_file(
+                        &client_socket,
+                        &mut netascii_writer,
+                        &transfer_options,
+                        &mut rtt_estimator,
+                        ACKPacket { block: 0 }.as_bytes(),
+                        pending_data_packet,
+                    )
+                    .map_err(|e| transfer_error(&client_socket, e))?;
+
+                    writer = netascii_writer
+                        .finish()
+                        .map_err(|e| abort_transfer(&client_socket, file_error_packet(&e)))?;
+
+                    received_file
                 }
+                _ => receive_file(
+                    &client_socket,
+                    &mut writer,
+                    &transfer_options,
+                    &mut rtt_estimator,
+                    ACKPacket { block: 0 }.as_bytes(),
+                    pending_data_packet,
+                )
+                .map_err(|e| transfer_error(&client_socket, e))?,
+            };
 
-                last_block_number = data_packet.block;
+            writer
+                .flush()
+                .map_err(|e| abort_transfer(&client_socket, file_error_packet(&e)))?;
+
+            drop(writer);
+
+            if let Some(partial_download) = partial_download {
+                partial_download.complete().map_err(|e| {
+                    let _ = client_socket.send(&file_error_packet(&e).as_bytes());
+                    local_file_error(e)
+                })?;
             }
+
+            // The final ACK packet is only sent once the file is
+            // stored, so that the server learns whether it succeeded
+            acknowledge_file(
+                &client_socket,
+                &received_file,
+                &transfer_options,
+                &rtt_estimator,
+            );
+
+            print_summary(
+                &client_args,
+                server_tid,
+                &local_path,
+                received_file.bytes,
+                &rtt_estimator,
+            );
+            Ok(())
         }
 
         ClientAction::Write => {
             let current_dir = env::current_dir()
                 .map_err(|e| format!("File transmission aborted due to an error: {}", e))?;
 
-            let mut file = File::open(current_dir.join(&client_args.filename))
-                .map_err(|e| format!("File transmission aborted due to an error: {}", e))?;
+            let local_path = current_dir.join(&client_args.local_filename);
+
+            // The size of the standard input is unknown
+            let (reader, file_size): (Box<dyn Read>, Option<u64>) = match client_args.uses_stdio() {
+                true => (Box::new(io::stdin().lock()), None),
+                false => {
+                    let mut file = File::open(&local_path)
+                        .map_err(|e| format!("File transmission aborted due to an error: {}", e))?;
+
+                    // In netascii, the size of the file is
+                    // the size of the translated data
+                    let file_size = match client_args.mode {
+                        TransferMode::Netascii => {
+                            netascii_size(&mut file).and_then(|size| file.rewind().map(|_| size))
+                        }
+                        _ => file.metadata().map(|metadata| metadata.len()),
+                    }
+                    .map_err(|e| format!("File transmission aborted due to an error: {}", e))?;
 
-            let wrq = WRQPacket::create_wrq_packet(&client_args.filename, "octet");
+                    (Box::new(file), Some(file_size))
+                }
+            };
 
-            client_socket
-                .send_to(&wrq, format!("{}:69", client_args.remote_ip))
-                .map_err(|e| format!("Unable to initialize a WRQ packet to the server: {}", e))?;
+            // Local text is sent as netascii
+            let mut reader: Box<dyn Read> = match client_args.mode {
+                TransferMode::Netascii => Box::new(NetasciiReader::new(reader)),
+                _ => reader,
+            };
 
-            // Create a buffer to store the
-            // first TFTP ACK packet for write
-            // - Opcode: 2 bytes
-            // - Block number: 2 bytes
-            let mut response = [0_u8; 4];
-
-            let (_, server_addr) = client_socket
-                .recv_from(&mut response)
-                .map_err(|e| format!("Unable to receive a ACK packet from the server: {}", e))?;
-
-            // Ensure connection with the server
-            client_socket
-                .connect(server_addr)
-                .map_err(|e| format!("Unable to etablish a connection with the server: {}", e))?;
-
-            let ack_packet_for_write = match TFTPPacket::parse(&response) {
-                Ok(TFTPPacket::ACK(packet)) => packet,
-                Ok(TFTPPacket::ERROR(err_packet)) => {
-                    return Err(format!(
-                        "File transmission aborted due to an error: {}",
-                        err_packet.get_error_message()
+            // The size of the file is announced to
+            // the server so that it can refuse it (RFC 2349)
+            if let (true, Some(file_size)) = (client_args.transfer_size, file_size) {
+                requested_options.insert("tsize", &file_size.to_string());
+            }
+
+            let wrq = WRQPacket::create_wrq_packet(
+                &client_args.remote_filename,
+                client_args.mode,
+                &requested_options,
+            );
+
+            // Create a buffer to store the first response
+            // which is either an OACK or the TFTP ACK packet
+            // for write (block number 0)
+            let mut response = [0_u8; 516];
+
+            let (client_socket, recv_packet_len, server_tid) = request_server(
+                &client_args,
+                &wrq,
+                &mut response,
+                &transfer_options,
+                &mut rtt_estimator,
+            )?;
+
+            // Packets from any other source than
+            // the server are rejected (RFC 1350)
+            let client_socket = TransferSocket::new(client_socket, server_tid, warn_unknown_tid);
+
+            match PacketRef::parse(&response[..recv_packet_len]) {
+                Ok(PacketRef::OACK(oack_packet)) => {
+                    apply_oack(
+                        &client_socket,
+                        &OACKPacket::from(&oack_packet),
+                        &requested_options,
+                        &mut transfer_options,
+                    )?;
+                }
+                // The server ignored the options
+                Ok(PacketRef::ACK(packet)) if packet.block == 0 => {}
+                Ok(PacketRef::ERROR(err_packet)) => {
+                    return Err(server_error(
+                        err_packet.get_error_code(),
+                        err_packet.get_error_message(),
                     ));
                 }
                 _ => {
-                    let err_packet = ERRORPacket::IllegalTftpOperation;
-                    client_socket.send(&err_packet.as_bytes());
-                    return Err(format!(
-                        "File transmission aborted due to an error: {}",
-                        err_packet.get_error_message()
-                    ));
+                    return Err(abort_transfer(
+                        &client_socket,
+                        ERRORPacket::IllegalTftpOperation,
+                    ))
                 }
-            };
+            }
 
-            if ack_packet_for_write.block != 0 {
-                let err_packet = ERRORPacket::IllegalTftpOperation;
-                client_socket.send(&err_packet.as_bytes());
-                return Err(format!(
-                    "File transmission aborted due to an error: {}",
-                    err_packet.get_error_message()
-                ));
+            // Refuse the upload before any data is sent
+            // when the file cannot be numbered without rollover
+            if file_size.is_some_and(|file_size| transfer_options.exceeds_block_numbers(file_size))
+            {
+                return Err(abort_transfer(&client_socket, too_large_error()));
             }
 
-            let mut current_block_number = 1;
+            // A negotiated timeout replaces the adaptive one
+            if !transfer_options.adaptive_timeout {
+                rtt_estimator = RttEstimator::new(&transfer_options);
+            }
 
-            loop {
-                let mut data_buffer = [0_u8; 512];
+            print_transfer_options(&client_args, server_tid, &transfer_options);
+
+            let sent_bytes = send_file(
+                &client_socket,
+                &mut reader,
+                &transfer_options,
+                &mut rtt_estimator,
+            )
+            .map_err(|e| transfer_error(&client_socket, e))?;
+
+            print_summary(
+                &client_args,
+                server_tid,
+                &local_path,
+                sent_bytes,
+                &rtt_estimator,
+            );
+            Ok(())
+        }
+    }
+}
 
-                let read_bytes = file
-                    .read(&mut data_buffer)
-                    .map_err(|e| format!("File transmission aborted due to an error: {}", e))?;
+/// Sends a request to the server and returns the socket which
+/// received the first response, along with its length and the
+/// address (TID) of the server.
+///
+/// The request is sent from a socket of the same address family
+/// as the server. With `--try-all`, each address resolved for the
+/// server is tried in turn until one of them answers.
+fn request_server(
+    client_args: &ClientArgs,
+    request: &[u8],
+    response: &mut [u8],
+    transfer_options: &TransferOptions,
+    rtt_estimator: &mut RttEstimator,
+) -> Result<(UdpSocket, usize, SocketAddr), String> {
+    let server_addrs = match client_args.try_all_addrs {
+        true => &client_args.server_addrs[..],
+        false => &client_args.server_addrs[..1],
+    };
 
-                let data_packet =
-                    DATAPacket::build(current_block_number, &data_buffer[..read_bytes]).unwrap();
+    let mut server_addrs = server_addrs.iter().peekable();
 
-                client_socket
-                    .send(&data_packet.as_bytes())
-                    .map_err(|e| format!("File transmission aborted due to an error: {}", e))?;
+    while let Some(&server_addr) = server_addrs.next() {
+        let local_ip = match server_addr {
+            SocketAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            SocketAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        };
 
-                // Create a buffer to store the ACK packet
-                let mut response = [0_u8; 4];
+        // The system picks the port (the TID of the client)
+        let client_socket = UdpSocket::bind(SocketAddr::new(local_ip, 0))
+            .map_err(|e| format!("Unable to initialize a UDP client socket: {}", e))?;
+
+        match send_request(
+            &client_socket,
+            request,
+            server_addr,
+            response,
+            transfer_options,
+            rtt_estimator,
+        ) {
+            Ok((recv_packet_len, server_tid)) => {
+                return Ok((client_socket, recv_packet_len, server_tid))
+            }
+            Err(e) => match server_addrs.peek() {
+                Some(next_addr) => {
+                    eprintln!("Warning: {} ({}), trying {}", e, server_addr, next_addr);
 
-                client_socket.recv(&mut response).map_err(|e| {
-                    format!("Unable to receive a ACK packet from the server: {}", e)
-                })?;
+                    // The round-trip time of the previous
+                    // address says nothing about this one
+                    *rtt_estimator = RttEstimator::new(transfer_options);
+                }
+                None => return Err(e),
+            },
+        }
+    }
 
-                let ack_packet = match TFTPPacket::parse(&response) {
-                    Ok(TFTPPacket::ACK(packet)) => packet,
-                    Ok(TFTPPacket::ERROR(err_packet)) => {
-                        return Err(format!(
-                            "File transmission aborted due to an error: {}",
-                            err_packet.get_error_message()
-                        ));
+    Err(String::from("Unable to resolve the server address"))
+}
+
+/// Applies the options acknowledged by the server in an OACK packet.
+///
+/// The OACK may only acknowledge options requested by the client
+/// with acceptable values (RFC 2347), otherwise the transfer is
+/// terminated with an ERROR packet (code 8).
+fn apply_oack(
+    client_socket: &TransferSocket,
+    oack_packet: &OACKPacket,
+    requested_options: &TFTPOptions,
+    transfer_options: &mut TransferOptions,
+) -> Result<(), String> {
+    for (name, value) in oack_packet.options.iter() {
+        let requested_value = requested_options.get(name);
+
+        let accepted = match (name, requested_value) {
+            // The server may only lower the block size (RFC 2348)
+            ("blksize", Some(requested_value)) => {
+                match (value.parse::<usize>(), requested_value.parse::<usize>()) {
+                    (Ok(block_size), Ok(requested_block_size))
+                        if (DATAPacket::MIN_BLOCK_SIZE..=requested_block_size)
+                            .contains(&block_size) =>
+                    {
+                        transfer_options.block_size = block_size;
+                        true
                     }
-                    _ => {
-                        let err_packet = ERRORPacket::IllegalTftpOperation;
-                        client_socket.send(&err_packet.as_bytes());
-                        return Err(format!(
-                            "File transmission aborted due to an error: {}",
-                            err_packet.get_error_message()
-                        ));
+                    _ => false,
+                }
+            }
+            // The server may only lower the window size (RFC 7440)
+            ("windowsize", Some(requested_value)) => {
+                match (value.parse::<usize>(), requested_value.parse::<usize>()) {
+                    (Ok(window_size), Ok(requested_window_size))
+                        if (1..=requested_window_size).contains(&window_size) =>
+                    {
+                        transfer_options.window_size = window_size;
+                        true
                     }
-                };
-
-                if ack_packet.block != current_block_number {
-                    let err_packet = ERRORPacket::IllegalTftpOperation;
-                    client_socket.send(&err_packet.as_bytes());
-                    return Err(format!(
-                        "File transmission aborted due to an error: {}",
-                        err_packet.get_error_message(),
-                    ));
+                    _ => false,
                 }
-
-                if read_bytes < 512 {
-                    println!("Upload completed!");
-                    return Ok(());
+            }
+            ("tsize", Some(_)) => match value.parse::<u64>() {
+                Ok(transfer_size) => {
+                    transfer_options.transfer_size = Some(transfer_size);
+                    true
+                }
+                Err(_) => false,
+            },
+            // The server must echo the requested timeout (RFC 2349)
+            ("timeout", Some(requested_value)) if value == requested_value => {
+                match value.parse::<u64>() {
+                    Ok(timeout) => {
+                        transfer_options.timeout = Duration::from_secs(timeout);
+                        transfer_options.adaptive_timeout = false;
+                        true
+                    }
+                    Err(_) => false,
                 }
-
-                current_block_number += 1;
             }
+            _ => false,
+        };
+
+        if !accepted {
+            return Err(abort_transfer(
+                client_socket,
+                ERRORPacket::OptionNegotiationFailed,
+            ));
         }
     }
 
     Ok(())
 }
+
+/// Prints the server TID and the options of the
+/// transfer, once negotiated, in verbose mode.
+///
+/// Like every status message, they are printed on the standard
+/// error, which keeps the standard output for streamed data.
+fn print_transfer_options(
+    client_args: &ClientArgs,
+    server_tid: SocketAddr,
+    transfer_options: &TransferOptions,
+) {
+    if !client_args.prints_status(Verbosity::Verbose) {
+        return;
+    }
+
+    eprintln!("Server: {}", server_tid);
+    eprintln!("Mode: {}", client_args.mode);
+    eprintln!("Block size: {} bytes", transfer_options.block_size);
+    eprintln!("Window size: {} blocks", transfer_options.window_size);
+
+    match transfer_options.adaptive_timeout {
+        true => eprintln!("Timeout: adaptive"),
+        false => eprintln!("Timeout: {:?}", transfer_options.timeout),
+    }
+}
+
+/// Prints the outcome of a completed transfer, with the amount
+/// of data transferred and the estimated round-trip time, either
+/// as text or as a single JSON object.
+fn print_summary(
+    client_args: &ClientArgs,
+    server_tid: SocketAddr,
+    local_path: &Path,
+    bytes: u64,
+    rtt_estimator: &RttEstimator,
+) {
+    let rtt = rtt_estimator.smoothed_rtt();
+
+    if client_args.output_format == OutputFormat::Json {
+        let operation = match client_args.action {
+            ClientAction::Read => "get",
+            ClientAction::Write => "put",
+        };
+
+        eprintln!(
+            "{{\"operation\":\"{}\",\"server\":\"{}\",\"remote_file\":{},\"local_file\":{},\"bytes\":{},\"rtt_ms\":{}}}",
+            operation,
+            server_tid,
+            json_string(&client_args.remote_filename),
+            json_string(&match client_args.uses_stdio() {
+                true => String::from("-"),
+                false => local_path.to_string_lossy().into_owned(),
+            }),
+            bytes,
+            rtt.map(|rtt| format!("{:.3}", rtt.as_secs_f64() * 1000.0))
+                .unwrap_or(String::from("null"))
+        );
+
+        return;
+    }
+
+    if !client_args.prints_status(Verbosity::Normal) {
+        return;
+    }
+
+    match client_args.action {
+        ClientAction::Read => {
+            eprintln!("Download completed!");
+            if !client_args.uses_stdio() {
+                eprintln!("File path: {}", local_path.display());
+            }
+        }
+        ClientAction::Write => eprintln!("Upload completed!"),
+    }
+
+    eprintln!("Transferred: {} bytes", bytes);
+
+    if let Some(rtt) = rtt {
+        eprintln!("Estimated RTT: {:.2?}", rtt);
+    }
+}
+
+/// Formats `value` as a JSON string literal.
+fn json_string(value: &str) -> String {
+    let mut literal = String::from("\"");
+
+    for c in value.chars() {
+        match c {
+            '"' => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            '\n' => literal.push_str("\\n"),
+            '\r' => literal.push_str("\\r"),
+            '\t' => literal.push_str("\\t"),
+            c if c.is_control() => literal.push_str(&format!("\\u{:04x}", c as u32)),
+            c => literal.push(c),
+        }
+    }
+
+    literal.push('"');
+    literal
+}
@@ -0,0 +1,150 @@
+use std::{
+    io,
+    net::{SocketAddr, UdpSocket},
+    time::Instant,
+};
+
+use tftppacket::ERRORPacket;
+use tftptransfer::{
+    reject_unknown_tid, RttEstimator, TransferError, TransferOptions, TransferSocket,
+};
+
+/// Sends a RRQ/WRQ packet to the server and waits for its first response,
+/// returning its length and the address of the server (its TID).
+///
+/// The server answers from a new port, so the response is accepted from
+/// any port of the server, while packets from other hosts are answered
+/// with an ERROR packet (code 5). The request is sent again each time
+/// the timeout expires.
+///
+/// Only the first response sets the TID of the server: the transfer then
+/// answers any other port, such as a session started by a retransmitted
+/// request, with an ERROR packet (code 5) (see `TransferSocket`).
+pub fn send_request(
+    client_socket: &UdpSocket,
+    request: &[u8],
+    server_addr: SocketAddr,
+    response: &mut [u8],
+    transfer_options: &TransferOptions,
+    rtt_estimator: &mut RttEstimator,
+) -> Result<(usize, SocketAddr), String> {
+    let mut timeouts = 0;
+
+    loop {
+        client_socket
+            .send_to(request, server_addr)
+            .map_err(|e| format!("Unable to initialize a request to the server: {}", e))?;
+
+        let sent_at = Instant::now();
+        let deadline = sent_at + rtt_estimator.rto();
+
+        let recv_info = loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+
+            // The socket does not accept a zero timeout
+            if timeout.is_zero() {
+                break None;
+            }
+
+            client_socket
+                .set_read_timeout(Some(timeout))
+                .map_err(|e| format!("Unable to initialize a request to the server: {}", e))?;
+
+            match client_socket.recv_from(response) {
+                Ok((len, source)) if source.ip() == server_addr.ip() => break Some((len, source)),
+                Ok((_, source)) => {
+                    reject_unknown_tid(client_socket, source);
+                    warn_unknown_tid(source);
+                }
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    ) =>
+                {
+                    break None
+                }
+                Err(e) => {
+                    return Err(format!(
+                        "Unable to receive a response from the server: {}",
+                        e
+                    ))
+                }
+            }
+        };
+
+        match recv_info {
+            Some(recv_info) => {
+                // Karn's algorithm: a retransmitted
+                // request gives no sample
+                if timeouts == 0 {
+                    rtt_estimator.add_sample(sent_at.elapsed());
+                }
+
+                return Ok(recv_info);
+            }
+            None => {
+                timeouts += 1;
+
+                if timeouts > transfer_options.retries {
+                    return Err(String::from(
+                        "Unable to receive a response from the server: Transfer timed out",
+                    ));
+                }
+
+                rtt_estimator.back_off();
+            }
+        }
+    }
+}
+
+/// Reports a packet rejected because it came
+/// from an unknown transfer ID.
+pub fn warn_unknown_tid(source: SocketAddr) {
+    eprintln!("Warning: packet from an unknown transfer ID ({})", source);
+}
+
+/// Describes an ERROR packet received from the server,
+/// reporting its error code apart from its message.
+pub fn server_error(error_code: u16, error_message: &str) -> String {
+    format!(
+        "File transmission aborted by the server [error code {}]: {}",
+        error_code, error_message
+    )
+}
+
+/// Converts a failure to read or write the local
+/// file into the ERROR packet sent to the server.
+pub fn file_error_packet(e: &io::Error) -> ERRORPacket {
+    match e.kind() {
+        // ENOSPC, EDQUOT and EFBIG
+        io::ErrorKind::StorageFull | io::ErrorKind::QuotaExceeded | io::ErrorKind::FileTooLarge => {
+            ERRORPacket::DiskFull
+        }
+        io::ErrorKind::AlreadyExists => ERRORPacket::FileAlreadyExists,
+        _ => ERRORPacket::NotDefined(e.to_string()),
+    }
+}
+
+/// Sends an ERROR packet to the server and
+/// returns the message reporting the abort.
+pub fn abort_transfer(client_socket: &TransferSocket, err_packet: ERRORPacket) -> String {
+    let _ = client_socket.send(&err_packet.as_bytes());
+    format!(
+        "File transmission aborted due to an error: {}",
+        err_packet.get_error_message()
+    )
+}
+
+/// Returns the message reporting the failure of a transfer, sending
+/// the server the ERROR packet matching a file error, if any.
+pub fn transfer_error(client_socket: &TransferSocket, transfer_error: TransferError) -> String {
+    match transfer_error {
+        TransferError::Peer { code, message } => server_error(code, &message),
+        TransferError::File(e) => abort_transfer(client_socket, file_error_packet(&e)),
+        transfer_error => format!(
+            "File transmission aborted due to an error: {}",
+            transfer_error
+        ),
+    }
+}
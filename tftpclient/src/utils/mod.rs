@@ -1,4 +1,51 @@
-use std::{env, net::IpAddr};
+use std::{
+    env,
+    ffi::OsStr,
+    net::{IpAddr, SocketAddr, ToSocketAddrs},
+    path::{Path, PathBuf},
+    process,
+};
+
+use tftppacket::{BlockRollover, DATAPacket, TransferMode};
+use tftptransfer::TransferOptions;
+
+use crate::download::OverwritePolicy;
+
+const USAGE: &str = "\
+Usage: tftpclient get [OPTIONS] <REMOTE> [LOCAL] <SERVER>
+       tftpclient put [OPTIONS] <LOCAL> [REMOTE] <SERVER>
+
+Transfers a file from or to a TFTP server (RFC 1350).
+
+Commands:
+  get, read              Download the file REMOTE from SERVER into LOCAL
+  put, write             Upload the file LOCAL to SERVER as REMOTE
+
+Arguments:
+  <REMOTE>, <LOCAL>      File on the server and on this host, which defaults
+                         to the last component of the name of the other one
+  <SERVER>               Server as HOST, HOST:PORT, IPv6 or [IPv6]:PORT
+
+Options:
+  -l, --local <FILE>     Local file, instead of LOCAL (- for stdout/stdin)
+  -r, --remote <FILE>    Remote file, instead of REMOTE
+  -m, --mode <MODE>      Transfer mode: netascii or octet [default: octet]
+  -p, --port <PORT>      Port of a SERVER given without one [default: 69]
+      --no-clobber       Refuse to replace the local file of a download (default)
+      --overwrite        Replace the local file of a download
+      --backup           Rename the local file of a download with a trailing ~
+      --blksize <SIZE>   Request a block size of 8..65464 bytes (RFC 2348)
+      --windowsize <N>   Request a window of 1..65535 blocks (RFC 7440)
+      --tsize            Request or announce the file size (RFC 2349)
+      --timeout <SECS>   Request a timeout of 1..255 seconds (RFC 2349)
+      --retries <COUNT>  Retransmissions before giving up [default: 5]
+      --rollover <MODE>  Block number after 65535: 0, 1 or none [default: 0]
+      --try-all          Try every address resolved for SERVER in turn
+  -v, --verbose          Print the details of the transfer
+  -q, --quiet            Print errors only
+      --format <FORMAT>  Format of the summary: text or json [default: text]
+  -h, --help             Print this help
+  -V, --version          Print the version";
 
 /// Represents the actions that a TFTP client can perform.
 ///
@@ -10,48 +57,364 @@ pub enum ClientAction {
     Write,
 }
 
+/// Amount of status messages printed by the client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
+}
+
+/// Format of the summary printed at the end of a transfer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
 /// Contains all arguments required by a TFTP client.
 ///
 /// This struct holds the necessary parameters for performing TFTP operations,
-/// including the action to be taken, the target filename and the remote IP address
-/// of the server.
+/// including the action to be taken, the local and remote filenames, the
+/// addresses resolved for the server, the options to negotiate (RFC 2348,
+/// RFC 2349, RFC 7440) and how the client reports the transfer.
 #[derive(Debug)]
 pub struct ClientArgs {
     pub action: ClientAction,
-    pub filename: String,
-    pub remote_ip: IpAddr,
+    pub local_filename: PathBuf,
+    pub remote_filename: String,
+    pub mode: TransferMode,
+    pub server_addrs: Vec<SocketAddr>,
+    pub try_all_addrs: bool,
+    pub overwrite_policy: OverwritePolicy,
+    pub block_size: Option<usize>,
+    pub window_size: Option<usize>,
+    pub transfer_size: bool,
+    pub timeout: Option<u64>,
+    pub retries: u32,
+    pub rollover: BlockRollover,
+    pub verbosity: Verbosity,
+    pub output_format: OutputFormat,
 }
 
 impl ClientArgs {
+    pub const DEFAULT_PORT: u16 = 69;
+
+    /// Local name of the standard input or output.
+    pub const STDIO: &'static str = "-";
+
     /// Constructs a new instance of `ClientArgs`.
+    ///
+    /// `--help` and `--version` print their text and exit.
     pub fn build() -> Result<Self, String> {
-        let args = env::args().collect::<Vec<String>>();
+        let args = env::args().skip(1).collect::<Vec<String>>();
+
+        if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+            println!("{}", USAGE);
+            process::exit(0);
+        }
+
+        if args.iter().any(|arg| arg == "-V" || arg == "--version") {
+            println!("tftpclient {}", env!("CARGO_PKG_VERSION"));
+            process::exit(0);
+        }
+
+        let command = args.first().ok_or(usage_error("Missing <COMMAND>"))?;
+
+        // `read` and `write` are kept from
+        // the former command line of the client
+        let action = match command.to_lowercase().as_str() {
+            "get" | "read" => ClientAction::Read,
+            "put" | "write" => ClientAction::Write,
+            _ => return Err(usage_error(&format!("Invalid <COMMAND>: {}", command))),
+        };
+
+        let mut positionals = Vec::new();
+        let mut local_filename = None;
+        let mut remote_filename = None;
+        let mut mode = TransferMode::Octet;
+        let mut port = Self::DEFAULT_PORT;
+        let mut overwrite_policy = OverwritePolicy::NoClobber;
+        let mut block_size = None;
+        let mut window_size = None;
+        let mut transfer_size = false;
+        let mut timeout = None;
+        let mut retries = TransferOptions::DEFAULT_RETRIES;
+        let mut rollover = BlockRollover::ToZero;
+        let mut try_all_addrs = false;
+        let mut verbosity = Verbosity::Normal;
+        let mut output_format = OutputFormat::Text;
+
+        let mut options = args[1..].iter();
+
+        while let Some(option) = options.next() {
+            match option.as_str() {
+                "-l" | "--local" => {
+                    let value = options
+                        .next()
+                        .ok_or(String::from("Missing value for [--local]"))?;
+
+                    local_filename = Some(PathBuf::from(value));
+                }
+                "-r" | "--remote" => {
+                    let value = options
+                        .next()
+                        .ok_or(String::from("Missing value for [--remote]"))?;
+
+                    remote_filename = Some(value.clone());
+                }
+                "-m" | "--mode" => {
+                    let value = options
+                        .next()
+                        .ok_or(String::from("Missing value for [--mode]"))?;
+
+                    mode = match value.parse::<TransferMode>() {
+                        Ok(mode @ (TransferMode::Netascii | TransferMode::Octet)) => mode,
+                        _ => return Err(String::from("Invalid [--mode] (netascii or octet)")),
+                    };
+                }
+                "-p" | "--port" => {
+                    let value = options
+                        .next()
+                        .ok_or(String::from("Missing value for [--port]"))?;
+
+                    match value.parse::<u16>() {
+                        Ok(number @ 1..=65535) => port = number,
+                        _ => return Err(String::from("Invalid [--port] (1..65535)")),
+                    }
+                }
+                "--no-clobber" => overwrite_policy = OverwritePolicy::NoClobber,
+                "--overwrite" => overwrite_policy = OverwritePolicy::Overwrite,
+                "--backup" => overwrite_policy = OverwritePolicy::Backup,
+                "--blksize" => {
+                    let value = options
+                        .next()
+                        .ok_or(String::from("Missing value for [--blksize]"))?;
+
+                    match value.parse::<usize>() {
+                        Ok(size)
+                            if (DATAPacket::MIN_BLOCK_SIZE..=DATAPacket::MAX_BLOCK_SIZE)
+                                .contains(&size) =>
+                        {
+                            block_size = Some(size)
+                        }
+                        _ => return Err(String::from("Invalid [--blksize] (8..65464)")),
+                    }
+                }
+                "--windowsize" => {
+                    let value = options
+                        .next()
+                        .ok_or(String::from("Missing value for [--windowsize]"))?;
+
+                    match value.parse::<usize>() {
+                        Ok(size @ 1..=65535) => window_size = Some(size),
+                        _ => return Err(String::from("Invalid [--windowsize] (1..65535)")),
+                    }
+                }
+                "--tsize" => transfer_size = true,
+                "--timeout" => {
+                    let value = options
+                        .next()
+                        .ok_or(String::from("Missing value for [--timeout]"))?;
+
+                    match value.parse::<u64>() {
+                        Ok(seconds @ 1..=255) => timeout = Some(seconds),
+                        _ => return Err(String::from("Invalid [--timeout] (1..255)")),
+                    }
+                }
+                "--retries" => {
+                    let value = options
+                        .next()
+                        .ok_or(String::from("Missing value for [--retries]"))?;
 
-        if args.len() != 4 {
-            return Err(String::from("Invalid arguments"));
+                    match value.parse::<u32>() {
+                        Ok(count) => retries = count,
+                        Err(_) => return Err(String::from("Invalid [--retries]")),
+                    }
+                }
+                "--rollover" => {
+                    let value = options
+                        .next()
+                        .ok_or(String::from("Missing value for [--rollover]"))?;
+
+                    rollover = match value.as_str() {
+                        "0" => BlockRollover::ToZero,
+                        "1" => BlockRollover::ToOne,
+                        "none" => BlockRollover::Disabled,
+                        _ => return Err(String::from("Invalid [--rollover] (0, 1 or none)")),
+                    };
+                }
+                "--try-all" => try_all_addrs = true,
+                "-v" | "--verbose" => verbosity = Verbosity::Verbose,
+                "-q" | "--quiet" => verbosity = Verbosity::Quiet,
+                "--format" => {
+                    let value = options
+                        .next()
+                        .ok_or(String::from("Missing value for [--format]"))?;
+
+                    output_format = match value.as_str() {
+                        "text" => OutputFormat::Text,
+                        "json" => OutputFormat::Json,
+                        _ => return Err(String::from("Invalid [--format] (text or json)")),
+                    };
+                }
+                // Everything after `--` is an argument
+                "--" => positionals.extend(options.by_ref()),
+                _ if option.starts_with('-') && option.len() > 1 => {
+                    return Err(usage_error(&format!("Invalid option: {}", option)))
+                }
+                _ => positionals.push(option),
+            }
         }
 
-        let action;
+        let file_arg = match action {
+            ClientAction::Read => "<REMOTE>",
+            ClientAction::Write => "<LOCAL>",
+        };
+
+        let (filename, other_filename, server) = match positionals[..] {
+            [filename, server] => (filename, None, server),
+            [filename, other_filename, server] => (filename, Some(other_filename), server),
+            [] => return Err(usage_error(&format!("Missing {}", file_arg))),
+            [_] => return Err(usage_error("Missing <SERVER>")),
+            [_, _, _, unexpected, ..] => {
+                return Err(usage_error(&format!("Unexpected argument: {}", unexpected)))
+            }
+        };
 
-        match args[1].to_lowercase().as_str() {
-            "read" => action = ClientAction::Read,
-            "write" => action = ClientAction::Write,
-            _ => return Err(String::from("Invalid [ACTION]")),
+        if filename.is_empty() {
+            return Err(usage_error(&format!("{} is empty", file_arg)));
         }
 
-        if args[2].is_empty() {
-            return Err(String::from("[FILENAME] is empty"));
+        // The second file is the local file of a
+        // download or the remote file of an upload
+        match (&action, other_filename) {
+            (_, None) => {}
+            (ClientAction::Read, Some(_)) if local_filename.is_some() => {
+                return Err(usage_error("[LOCAL] is given along with [--local]"))
+            }
+            (ClientAction::Read, Some(name)) => local_filename = Some(PathBuf::from(name)),
+            (ClientAction::Write, Some(_)) if remote_filename.is_some() => {
+                return Err(usage_error("[REMOTE] is given along with [--remote]"))
+            }
+            (ClientAction::Write, Some(name)) => remote_filename = Some(name.clone()),
         }
 
-        let remote_ip = match args[3].parse::<IpAddr>() {
-            Ok(ip) => ip,
-            Err(_) => return Err(String::from("Invalid [REMOTE IP ADDRESS]")),
+        // Without a name on the other side, the file
+        // keeps the last component of its name
+        let local_filename = match (local_filename, &action) {
+            (Some(local_filename), _) => local_filename,
+            (None, ClientAction::Read) => PathBuf::from(base_name(filename, file_arg)?),
+            (None, ClientAction::Write) => PathBuf::from(filename),
+        };
+
+        let remote_filename = match (remote_filename, &action) {
+            (Some(remote_filename), _) => remote_filename,
+            (None, ClientAction::Read) => filename.clone(),
+            // The standard input has no name to give to the file
+            (None, ClientAction::Write) if filename == Self::STDIO => {
+                return Err(usage_error("Missing [REMOTE] for an upload from stdin"))
+            }
+            (None, ClientAction::Write) => base_name(filename, file_arg)?.to_string(),
         };
 
+        if local_filename.as_os_str().is_empty() || remote_filename.is_empty() {
+            return Err(usage_error("Empty file name"));
+        }
+
+        let server_addrs = resolve_server(server, port)?;
+
         Ok(Self {
             action,
-            filename: args[2].clone(),
-            remote_ip,
+            local_filename,
+            remote_filename,
+            mode,
+            server_addrs,
+            try_all_addrs,
+            overwrite_policy,
+            block_size,
+            window_size,
+            transfer_size,
+            timeout,
+            retries,
+            rollover,
+            verbosity,
+            output_format,
         })
     }
+
+    /// Tells whether the transfer streams the standard
+    /// output of a download or the standard input of an upload.
+    pub fn uses_stdio(&self) -> bool {
+        self.local_filename.as_os_str() == Self::STDIO
+    }
+
+    /// Tells whether the status messages of `verbosity`
+    /// are printed, which a JSON summary replaces.
+    pub fn prints_status(&self, verbosity: Verbosity) -> bool {
+        self.output_format == OutputFormat::Text && self.verbosity >= verbosity
+    }
+}
+
+/// Returns the last component of `filename`,
+/// such as `fw.bin` for `build/out/fw.bin`.
+fn base_name<'a>(filename: &'a str, file_arg: &str) -> Result<&'a str, String> {
+    Path::new(filename)
+        .file_name()
+        .and_then(OsStr::to_str)
+        .ok_or(format!("Invalid {}: {} (no file name)", file_arg, filename))
+}
+
+/// Formats an error in the use of the command line.
+fn usage_error(message: &str) -> String {
+    format!("{} (see tftpclient --help)", message)
+}
+
+/// Resolves the server, given as `host`, `host:port`, an IPv6
+/// address or `[IPv6]:port`, into the addresses to try in order.
+///
+/// Hostnames are resolved by the system resolver, and
+/// servers without a port use `default_port`.
+fn resolve_server(value: &str, default_port: u16) -> Result<Vec<SocketAddr>, String> {
+    let invalid_server = || usage_error(&format!("Invalid <SERVER>: {}", value));
+
+    // An IPv6 address without brackets has no port
+    if let Ok(ip) = value.parse::<IpAddr>() {
+        return Ok(vec![SocketAddr::new(ip, default_port)]);
+    }
+
+    let (host, port) = match value.strip_prefix('[') {
+        Some(rest) => match rest.split_once(']') {
+            Some((host, "")) => (host, None),
+            Some((host, port)) => match port.strip_prefix(':') {
+                Some(port) => (host, Some(port)),
+                None => return Err(invalid_server()),
+            },
+            None => return Err(invalid_server()),
+        },
+        None => match value.rsplit_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (value, None),
+        },
+    };
+
+    let port = match port.map(str::parse::<u16>) {
+        Some(Ok(port)) => port,
+        Some(Err(_)) => return Err(invalid_server()),
+        None => default_port,
+    };
+
+    if host.is_empty() {
+        return Err(invalid_server());
+    }
+
+    let server_addrs = (host, port)
+        .to_socket_addrs()
+        .map_err(|e| format!("Unable to resolve {}: {}", host, e))?
+        .collect::<Vec<SocketAddr>>();
+
+    match server_addrs.is_empty() {
+        true => Err(format!("Unable to resolve {}: No address found", host)),
+        false => Ok(server_addrs),
+    }
 }
@@ -0,0 +1,427 @@
+use crate::{
+    reader::PacketReader, ACKPacket, DATAPacket, ERRORPacket, OACKPacket, PacketError, RRQPacket,
+    Strictness, TFTPOptions, TFTPPacket, TransferMode, WRQPacket,
+};
+
+/// Represents the options of a TFTP RRQ/WRQ/OACK packet,
+/// borrowed from the received bytes (RFC 2347).
+///
+/// Options are kept as received, including their case
+/// and repetitions; lookups ignore the case of names
+/// and return the first occurrence.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OptionsRef<'a> {
+    options: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> OptionsRef<'a> {
+    /// Get the value of the option with the specified name.
+    pub fn get(&self, name: &str) -> Option<&'a str> {
+        self.options
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| *v)
+    }
+
+    /// Returns `true` if there is no option.
+    pub fn is_empty(&self) -> bool {
+        self.options.is_empty()
+    }
+
+    /// Iterates over the options as `(name, value)` pairs.
+    pub fn iter(&self) -> impl Iterator<Item = (&'a str, &'a str)> + '_ {
+        self.options.iter().copied()
+    }
+
+    /// Reads the null-terminated option name/value
+    /// pairs up to the end of the packet.
+    ///
+    /// In lenient mode, an option without a value or with
+    /// an empty name (such as padding) ends the options.
+    fn read(reader: &mut PacketReader<'a>) -> Result<Self, PacketError> {
+        let mut options = Vec::new();
+
+        let lenient = reader.strictness() == Strictness::Lenient;
+
+        while !reader.is_at_end() {
+            let name_offset = reader.offset();
+
+            // Option name
+            let name = reader.string("option name")?;
+
+            if name.is_empty() {
+                if lenient {
+                    break;
+                }
+
+                return Err(PacketError::EmptyOptionName {
+                    offset: name_offset,
+                });
+            }
+
+            // Option value
+            let value = match reader.string("option value") {
+                Ok(value) => value,
+                Err(PacketError::Truncated { .. }) if lenient => break,
+                Err(e) => return Err(e),
+            };
+
+            options.push((name, value));
+        }
+
+        reader.finish()?;
+
+        Ok(Self { options })
+    }
+}
+
+impl From<&OptionsRef<'_>> for TFTPOptions {
+    /// Copies the options, keeping only the
+    /// first occurrence of each name.
+    fn from(options_ref: &OptionsRef<'_>) -> Self {
+        let mut options = Self::new();
+
+        for (name, value) in options_ref.iter() {
+            if options.get(name).is_none() {
+                options.insert(name, value);
+            }
+        }
+
+        options
+    }
+}
+
+/// Represents a TFTP RRQ/WRQ packet,
+/// borrowed from the received bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestRef<'a> {
+    pub filename: &'a str,
+    pub mode: TransferMode,
+    pub options: OptionsRef<'a>,
+}
+
+impl<'a> RequestRef<'a> {
+    /// Reads the fields following the opcode.
+    pub(crate) fn read(reader: &mut PacketReader<'a>) -> Result<Self, PacketError> {
+        let filename = reader.string("filename")?;
+        let mode = TransferMode::read(reader)?;
+
+        // Obtain the options that follow the mode (RFC 2347)
+        let options = OptionsRef::read(reader)?;
+
+        Ok(Self {
+            filename,
+            mode,
+            options,
+        })
+    }
+}
+
+impl From<&RequestRef<'_>> for RRQPacket {
+    fn from(request: &RequestRef<'_>) -> Self {
+        Self {
+            filename: request.filename.to_string(),
+            mode: request.mode,
+            options: TFTPOptions::from(&request.options),
+        }
+    }
+}
+
+impl From<&RequestRef<'_>> for WRQPacket {
+    fn from(request: &RequestRef<'_>) -> Self {
+        Self {
+            filename: request.filename.to_string(),
+            mode: request.mode,
+            options: TFTPOptions::from(&request.options),
+        }
+    }
+}
+
+/// Represents a TFTP DATA packet,
+/// borrowed from the received bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DATAPacketRef<'a> {
+    pub block: u16,
+    data: &'a [u8],
+}
+
+impl<'a> DATAPacketRef<'a> {
+    /// Reads the fields following the opcode.
+    pub(crate) fn read(reader: &mut PacketReader<'a>) -> Result<Self, PacketError> {
+        // The maximum length of a TFTP DATA packet
+        // is equal to 65468 bytes:
+        // - Opcode: 2 bytes
+        // - Block number: 2 bytes
+        // - Data: up to 65464 bytes (RFC 2348)
+        if reader.len() > DATAPacket::MAX_BLOCK_SIZE + 4 {
+            return Err(PacketError::Oversized {
+                size: reader.len(),
+                max: DATAPacket::MAX_BLOCK_SIZE + 4,
+            });
+        }
+
+        let block = reader.u16("block number")?;
+
+        Ok(Self {
+            block,
+            data: reader.rest(),
+        })
+    }
+
+    /// Get the data in a TFTP DATA packet
+    /// (`DATAPacketRef`) in byte format.
+    pub fn get_data(&self) -> &'a [u8] {
+        self.data
+    }
+}
+
+impl From<&DATAPacketRef<'_>> for DATAPacket {
+    fn from(packet: &DATAPacketRef<'_>) -> Self {
+        Self {
+            block: packet.block,
+            data: packet.data.to_vec(),
+        }
+    }
+}
+
+impl<'a> From<&'a DATAPacket> for DATAPacketRef<'a> {
+    fn from(packet: &'a DATAPacket) -> Self {
+        Self {
+            block: packet.block,
+            data: &packet.data,
+        }
+    }
+}
+
+/// Represents a TFTP ERROR packet,
+/// borrowed from the received bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ERRORPacketRef<'a> {
+    error_code: u16,
+    error_message: &'a str,
+}
+
+impl<'a> ERRORPacketRef<'a> {
+    /// Reads the fields following the opcode.
+    pub(crate) fn read(reader: &mut PacketReader<'a>) -> Result<Self, PacketError> {
+        let error_code = reader.u16("error code")?;
+
+        // In lenient mode, the error message may be missing
+        let error_message = match reader.is_at_end() && reader.strictness() == Strictness::Lenient {
+            true => "",
+            false => reader.string("error message")?,
+        };
+
+        reader.finish()?;
+
+        if error_code > 8 {
+            return Err(PacketError::UnknownErrorCode(error_code));
+        }
+
+        Ok(Self {
+            error_code,
+            error_message,
+        })
+    }
+
+    /// Retrieve the error code from a TFTP ERROR packet.
+    pub fn get_error_code(&self) -> u16 {
+        self.error_code
+    }
+
+    /// Retrieve the error message from a TFTP
+    /// ERROR packet, as sent by the peer.
+    pub fn get_error_message(&self) -> &'a str {
+        self.error_message
+    }
+}
+
+impl From<&ERRORPacketRef<'_>> for ERRORPacket {
+    /// Only the message of an error not defined
+    /// by the RFCs (code 0) is kept.
+    fn from(packet: &ERRORPacketRef<'_>) -> Self {
+        match packet.error_code {
+            1 => Self::FileNotFound,
+            2 => Self::AccessViolation,
+            3 => Self::DiskFull,
+            4 => Self::IllegalTftpOperation,
+            5 => Self::UknownTransferID,
+            6 => Self::FileAlreadyExists,
+            7 => Self::NoSuchUser,
+            8 => Self::OptionNegotiationFailed,
+            _ => Self::NotDefined(packet.error_message.to_string()),
+        }
+    }
+}
+
+/// Represents a TFTP OACK packet (RFC 2347),
+/// borrowed from the received bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OACKPacketRef<'a> {
+    pub options: OptionsRef<'a>,
+}
+
+impl<'a> OACKPacketRef<'a> {
+    /// Reads the fields following the opcode.
+    pub(crate) fn read(reader: &mut PacketReader<'a>) -> Result<Self, PacketError> {
+        let options = OptionsRef::read(reader)?;
+
+        Ok(Self { options })
+    }
+}
+
+impl From<&OACKPacketRef<'_>> for OACKPacket {
+    fn from(packet: &OACKPacketRef<'_>) -> Self {
+        Self {
+            options: TFTPOptions::from(&packet.options),
+        }
+    }
+}
+
+/// Represents all TFTP packets described in RFC 1350
+/// and RFC 2347, parsed in place from the received
+/// bytes: strings and data are borrowed, not copied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PacketRef<'a> {
+    RRQ(RequestRef<'a>),
+    WRQ(RequestRef<'a>),
+    DATA(DATAPacketRef<'a>),
+    ACK(ACKPacket),
+    ERROR(ERRORPacketRef<'a>),
+    OACK(OACKPacketRef<'a>),
+}
+
+impl<'a> PacketRef<'a> {
+    /// Parses a raw byte slice into a `PacketRef`
+    /// (see [`Strictness::Lenient`]).
+    pub fn parse(data: &'a [u8]) -> Result<Self, PacketError> {
+        Self::parse_with(data, Strictness::default())
+    }
+
+    /// Parses a raw byte slice into a `PacketRef`
+    /// of the type designated by its opcode, with
+    /// the specified strictness.
+    pub fn parse_with(data: &'a [u8], strictness: Strictness) -> Result<Self, PacketError> {
+        let mut reader = PacketReader::new(data, strictness);
+
+        let opcode = reader.u16("opcode")?;
+
+        match opcode {
+            RRQPacket::OPCODE => RequestRef::read(&mut reader).map(Self::RRQ),
+            WRQPacket::OPCODE => RequestRef::read(&mut reader).map(Self::WRQ),
+            DATAPacket::OPCODE => DATAPacketRef::read(&mut reader).map(Self::DATA),
+            ACKPacket::OPCODE => ACKPacket::read(&mut reader).map(Self::ACK),
+            ERRORPacket::OPCODE => ERRORPacketRef::read(&mut reader).map(Self::ERROR),
+            OACKPacket::OPCODE => OACKPacketRef::read(&mut reader).map(Self::OACK),
+            _ => Err(PacketError::UnknownOpcode(opcode)),
+        }
+    }
+}
+
+impl From<&PacketRef<'_>> for TFTPPacket {
+    /// Copies the strings and data of the packet.
+    fn from(packet: &PacketRef<'_>) -> Self {
+        match packet {
+            PacketRef::RRQ(request) => Self::RRQ(RRQPacket::from(request)),
+            PacketRef::WRQ(request) => Self::WRQ(WRQPacket::from(request)),
+            PacketRef::DATA(packet) => Self::DATA(DATAPacket::from(packet)),
+            PacketRef::ACK(packet) => Self::ACK(*packet),
+            PacketRef::ERROR(packet) => Self::ERROR(ERRORPacket::from(packet)),
+            PacketRef::OACK(packet) => Self::OACK(OACKPacket::from(packet)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn duplicate_options_keep_the_first_occurrence() {
+        let rrq = b"\x00\x01file\x00octet\x00blksize\x00512\x00BLKSIZE\x001024\x00";
+
+        let request = match PacketRef::parse(rrq).unwrap() {
+            PacketRef::RRQ(request) => request,
+            packet => panic!("expected a RRQ packet, parsed {:?}", packet),
+        };
+
+        // The borrowed options are kept as received
+        assert_eq!(
+            request.options.iter().collect::<Vec<_>>(),
+            [("blksize", "512"), ("BLKSIZE", "1024")]
+        );
+        assert_eq!(request.options.get("BlkSize"), Some("512"));
+
+        let rrq = RRQPacket::from(&request);
+
+        assert_eq!(rrq.options.iter().collect::<Vec<_>>(), [("blksize", "512")]);
+    }
+
+    #[test]
+    fn data_packets_convert_both_ways() {
+        let bytes = DATAPacket::build(7, b"block").unwrap().as_bytes();
+
+        let packet = match PacketRef::parse(&bytes).unwrap() {
+            PacketRef::DATA(packet) => packet,
+            packet => panic!("expected a DATA packet, parsed {:?}", packet),
+        };
+
+        // The data is borrowed from the received bytes
+        assert!(std::ptr::eq(packet.get_data(), &bytes[4..]));
+
+        let owned = DATAPacket::from(&packet);
+
+        assert_eq!(owned.block, 7);
+        assert_eq!(owned.get_data(), b"block");
+        assert_eq!(DATAPacketRef::from(&owned), packet);
+    }
+
+    #[test]
+    fn packets_convert_to_owned_packets() {
+        let wrq = WRQPacket::create_wrq_packet("file", TransferMode::Octet, &TFTPOptions::new());
+
+        match TFTPPacket::parse(&wrq).unwrap() {
+            TFTPPacket::WRQ(wrq) => {
+                assert_eq!(wrq.filename, "file");
+                assert_eq!(wrq.mode, TransferMode::Octet);
+            }
+            packet => panic!("expected a WRQ packet, parsed {:?}", packet),
+        }
+
+        assert!(matches!(
+            TFTPPacket::parse(&ACKPacket { block: 3 }.as_bytes()).unwrap(),
+            TFTPPacket::ACK(ACKPacket { block: 3 })
+        ));
+
+        match TFTPPacket::parse(b"\x00\x06tsize\x0042\x00").unwrap() {
+            TFTPPacket::OACK(oack) => assert_eq!(oack.options.get("tsize"), Some("42")),
+            packet => panic!("expected an OACK packet, parsed {:?}", packet),
+        }
+    }
+
+    #[test]
+    fn error_packets_keep_the_message_of_undefined_errors() {
+        let bytes = ERRORPacket::NotDefined("quota".to_string()).as_bytes();
+
+        let packet = match PacketRef::parse(&bytes).unwrap() {
+            PacketRef::ERROR(packet) => packet,
+            packet => panic!("expected an ERROR packet, parsed {:?}", packet),
+        };
+
+        assert_eq!(packet.get_error_code(), 0);
+        assert_eq!(packet.get_error_message(), "quota");
+        assert_eq!(ERRORPacket::from(&packet).get_error_message(), "quota");
+
+        // The standard message replaces the one of a defined error
+        let packet = match PacketRef::parse(b"\x00\x05\x00\x01missing\x00").unwrap() {
+            PacketRef::ERROR(packet) => packet,
+            packet => panic!("expected an ERROR packet, parsed {:?}", packet),
+        };
+
+        assert_eq!(packet.get_error_message(), "missing");
+        assert!(matches!(
+            ERRORPacket::from(&packet),
+            ERRORPacket::FileNotFound
+        ));
+    }
+}
@@ -0,0 +1,65 @@
+use std::{error, fmt};
+
+/// Represents the reasons why a byte slice is not a valid TFTP packet.
+///
+/// Offsets are counted in bytes from the start of the packet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PacketError {
+    /// The packet ends at `offset`, where `field` was expected.
+    Truncated { offset: usize, field: &'static str },
+    /// The opcode belongs to another type of packet.
+    WrongOpcode { expected: u16, found: u16 },
+    /// The opcode is not defined by any RFC.
+    UnknownOpcode(u16),
+    /// The string `field` starting at `offset` has no null byte.
+    MissingTerminator { offset: usize, field: &'static str },
+    /// The string `field` starting at `offset` is not valid UTF-8.
+    InvalidUtf8 { offset: usize, field: &'static str },
+    /// The packet is `size` bytes long, beyond `max` bytes.
+    Oversized { size: usize, max: usize },
+    /// The mode starting at `offset` is not a transfer mode.
+    UnknownMode { offset: usize, mode: String },
+    /// The option starting at `offset` has an empty name.
+    EmptyOptionName { offset: usize },
+    /// The error code is not defined by any RFC.
+    UnknownErrorCode(u16),
+    /// Bytes follow the end of the packet at `offset`.
+    TrailingBytes { offset: usize },
+}
+
+impl fmt::Display for PacketError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Truncated { offset, field } => {
+                write!(f, "Truncated packet: missing {} at byte {}", field, offset)
+            }
+            Self::WrongOpcode { expected, found } => {
+                write!(f, "Wrong opcode: expected {}, found {}", expected, found)
+            }
+            Self::UnknownOpcode(opcode) => write!(f, "Unknown opcode: {}", opcode),
+            Self::MissingTerminator { offset, field } => write!(
+                f,
+                "Missing null byte after the {} starting at byte {}",
+                field, offset
+            ),
+            Self::InvalidUtf8 { offset, field } => write!(
+                f,
+                "Invalid UTF-8 in the {} starting at byte {}",
+                field, offset
+            ),
+            Self::Oversized { size, max } => {
+                write!(f, "Oversized packet: {} bytes (at most {})", size, max)
+            }
+            Self::UnknownMode { offset, mode } => {
+                write!(f, "Unknown transfer mode {:?} at byte {}", mode, offset)
+            }
+            Self::EmptyOptionName { offset } => {
+                write!(f, "Empty option name at byte {}", offset)
+            }
+            Self::UnknownErrorCode(error_code) => write!(f, "Unknown error code: {}", error_code),
+            Self::TrailingBytes { offset } => write!(f, "Trailing bytes at byte {}", offset),
+        }
+    }
+}
+
+impl error::Error for PacketError {}
@@ -1,16 +1,241 @@
+use std::{fmt, str::FromStr};
+
+use reader::PacketReader;
+
+pub use borrowed::{
+    DATAPacketRef, ERRORPacketRef, OACKPacketRef, OptionsRef, PacketRef, RequestRef,
+};
+pub use error::PacketError;
+pub use netascii::{netascii_size, NetasciiReader, NetasciiWriter};
+
+mod borrowed;
+mod error;
+mod netascii;
+mod reader;
+
+/// Represents the options appended to a TFTP RRQ/WRQ packet
+/// or acknowledged by a TFTP OACK packet (RFC 2347).
+///
+/// Options are kept in the order in which they were received
+/// and their names are stored in lowercase, since option names
+/// are case-insensitive.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TFTPOptions {
+    options: Vec<(String, String)>,
+}
+
+impl TFTPOptions {
+    /// Constructs an empty `TFTPOptions` instance.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inserts an option, replacing the value of
+    /// an existing option with the same name.
+    pub fn insert(&mut self, name: &str, value: &str) {
+        let name = name.to_lowercase();
+
+        match self.options.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, v)) => *v = value.to_string(),
+            None => self.options.push((name, value.to_string())),
+        }
+    }
+
+    /// Get the value of the option with the specified name.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        let name = name.to_lowercase();
+
+        self.options
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Returns `true` if there is no option.
+    pub fn is_empty(&self) -> bool {
+        self.options.is_empty()
+    }
+
+    /// Iterates over the options as `(name, value)` pairs.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.options.iter().map(|(n, v)| (n.as_str(), v.as_str()))
+    }
+
+    /// Appends the options to a TFTP packet in byte format.
+    fn write_into(&self, packet: &mut Vec<u8>) {
+        for (name, value) in self.options.iter() {
+            // Option name
+            packet.extend_from_slice(name.as_bytes());
+
+            // Option null byte
+            packet.push(0);
+
+            // Option value
+            packet.extend_from_slice(value.as_bytes());
+
+            // Option null byte
+            packet.push(0);
+        }
+    }
+}
+
+/// Represents the value following the last block number (65535),
+/// which is not defined by RFC 1350.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockRollover {
+    /// The block number 65535 is followed by 0.
+    ToZero,
+    /// The block number 65535 is followed by 1.
+    ToOne,
+    /// The block number 65535 is the last one,
+    /// which limits the size of a transfer.
+    Disabled,
+}
+
+/// Represents the block number of a TFTP DATA or ACK packet.
+///
+/// Block numbers are 16-bit values, so a transfer of more than
+/// 65535 blocks rolls over according to a `BlockRollover`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockNumber(u16);
+
+impl BlockNumber {
+    /// Block number acknowledging a WRQ packet or an OACK packet.
+    pub const ZERO: Self = Self(0);
+
+    /// Largest number of blocks in a transfer without rollover.
+    pub const MAX_BLOCKS: u64 = u16::MAX as u64;
+
+    /// Get the block number in its 16-bit format.
+    pub fn value(self) -> u16 {
+        self.0
+    }
+
+    /// Get the block number following this one, or `None` when
+    /// the last one is reached and the rollover is disabled.
+    pub fn next(self, rollover: BlockRollover) -> Option<Self> {
+        match (self.0.checked_add(1), rollover) {
+            (Some(block), _) => Some(Self(block)),
+            (None, BlockRollover::ToZero) => Some(Self(0)),
+            (None, BlockRollover::ToOne) => Some(Self(1)),
+            (None, BlockRollover::Disabled) => None,
+        }
+    }
+
+    /// Get the number of blocks from `origin` to this block number,
+    /// or `None` when this block number cannot follow `origin`.
+    ///
+    /// The block number 0 only starts a transfer, so with a rollover
+    /// to 1 the cycle is made of the block numbers 1 to 65535.
+    pub fn offset_from(self, origin: Self, rollover: BlockRollover) -> Option<usize> {
+        let (block, origin) = (self.0 as usize, origin.0 as usize);
+
+        match rollover {
+            BlockRollover::ToZero => Some(block.wrapping_sub(origin) % 65536),
+            BlockRollover::ToOne if block == 0 => (origin == 0).then_some(0),
+            BlockRollover::ToOne if origin == 0 => Some(block),
+            BlockRollover::ToOne => Some((block + 65535 - origin) % 65535),
+            BlockRollover::Disabled => block.checked_sub(origin),
+        }
+    }
+}
+
+impl From<u16> for BlockNumber {
+    fn from(block: u16) -> Self {
+        Self(block)
+    }
+}
+
+impl From<BlockNumber> for u16 {
+    fn from(block: BlockNumber) -> Self {
+        block.0
+    }
+}
+
+/// Represents how closely a packet must follow the RFCs to be parsed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Strictness {
+    /// Every string ends with a null byte and
+    /// no byte follows the last field.
+    Strict,
+    /// Tolerates the deviations of buggy clients and boot ROMs:
+    /// a missing final null byte and bytes following the last field.
+    ///
+    /// This is the default, since some network stacks pad short packets,
+    /// such as ACK packets, up to a minimum frame size. The padding never
+    /// changes the fields that are read, so accepting it is harmless.
+    #[default]
+    Lenient,
+}
+
+/// Represents the transfer mode of a TFTP RRQ/WRQ packet (RFC 1350).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferMode {
+    /// Text, with the line endings of netascii (RFC 764).
+    Netascii,
+    /// Raw 8-bit bytes.
+    Octet,
+    /// Obsolete mode sending a file to a user.
+    Mail,
+}
+
+impl TransferMode {
+    /// Get the name of the mode, as sent in a RRQ/WRQ packet.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Netascii => "netascii",
+            Self::Octet => "octet",
+            Self::Mail => "mail",
+        }
+    }
+
+    /// Reads the mode of a RRQ/WRQ packet,
+    /// in any case (RFC 1350).
+    fn read(reader: &mut PacketReader) -> Result<Self, PacketError> {
+        let offset = reader.offset();
+        let name = reader.string("mode")?;
+
+        name.parse::<Self>().map_err(|_| PacketError::UnknownMode {
+            offset,
+            mode: name.to_string(),
+        })
+    }
+}
+
+impl FromStr for TransferMode {
+    type Err = String;
+
+    /// Parses a mode name, which is case-insensitive.
+    fn from_str(mode: &str) -> Result<Self, Self::Err> {
+        match mode.to_lowercase().as_str() {
+            "netascii" => Ok(Self::Netascii),
+            "octet" => Ok(Self::Octet),
+            "mail" => Ok(Self::Mail),
+            _ => Err(format!("Invalid transfer mode: {}", mode)),
+        }
+    }
+}
+
+impl fmt::Display for TransferMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 /// Represents a TFTP RRQ Packet.
 #[derive(Debug)]
 pub struct RRQPacket {
     pub filename: String,
-    pub mode: String,
+    pub mode: TransferMode,
+    pub options: TFTPOptions,
 }
 
 impl RRQPacket {
     pub const OPCODE: u16 = 1;
 
     /// Constructs a TFTP RRQ packet in byte format
-    /// using the specified filename and mode.
-    pub fn create_rrq_packet(filename: &str, mode: &str) -> Vec<u8> {
+    /// using the specified filename, mode and options.
+    pub fn create_rrq_packet(filename: &str, mode: TransferMode, options: &TFTPOptions) -> Vec<u8> {
         let mut packet: Vec<u8> = Vec::new();
 
         // RRQ opcode = 1
@@ -23,65 +248,31 @@ impl RRQPacket {
         packet.push(0);
 
         // RRQ mode
-        packet.extend_from_slice(mode.as_bytes());
+        packet.extend_from_slice(mode.as_str().as_bytes());
 
         // RRQ null byte
         packet.push(0);
 
+        // RRQ options (RFC 2347)
+        options.write_into(&mut packet);
+
         packet
     }
 
     /// Parses a raw byte slice into a `RRQPacket`
-    pub fn parse(data: &[u8]) -> Result<Self, String> {
-        let opcode = match data.get(0..2) {
-            Some(v) => u16::from_be_bytes(v.try_into().unwrap()),
-            None => return Err(String::from("Invalid RRQ packet")),
-        };
-
-        if opcode != Self::OPCODE {
-            return Err(String::from("Invalid RRQ packet"));
-        }
-
-        let mut data_iterator = data
-            .iter()
-            .enumerate()
-            .skip_while(|(i, _)| *i == 0 || *i == 1); // Skip opcode bytes
-
-        let mut filename_bytes: Vec<u8> = Vec::new();
-
-        // Obtain the filename in byte format
-        loop {
-            match data_iterator.next() {
-                Some((_, byte)) => {
-                    if *byte == 0 {
-                        break;
-                    }
-                    filename_bytes.push(*byte);
-                }
-                None => return Err(String::from("Invalid RRQ packet")),
-            }
-        }
-
-        let mut mode_bytes: Vec<u8> = Vec::new();
+    /// (see [`Strictness::Lenient`]).
+    pub fn parse(data: &[u8]) -> Result<Self, PacketError> {
+        Self::parse_with(data, Strictness::default())
+    }
 
-        // Obtain the mode in byte format
-        loop {
-            match data_iterator.next() {
-                Some((_, byte)) => {
-                    if *byte == 0 {
-                        break;
-                    }
-                    mode_bytes.push(*byte);
-                }
-                None => return Err(String::from("Invalid RRQ packet")),
-            }
-        }
+    /// Parses a raw byte slice into a `RRQPacket`
+    /// with the specified strictness.
+    pub fn parse_with(data: &[u8], strictness: Strictness) -> Result<Self, PacketError> {
+        let mut reader = PacketReader::new(data, strictness);
 
-        let filename =
-            String::from_utf8(filename_bytes).map_err(|_| String::from("Invalid RRQ packet"))?;
-        let mode = String::from_utf8(mode_bytes).map_err(|_| String::from("Invalid RRQ packet"))?;
+        reader.opcode(Self::OPCODE)?;
 
-        Ok(Self { filename, mode })
+        RequestRef::read(&mut reader).map(|request| Self::from(&request))
     }
 }
 
@@ -89,15 +280,16 @@ impl RRQPacket {
 #[derive(Debug)]
 pub struct WRQPacket {
     pub filename: String,
-    pub mode: String,
+    pub mode: TransferMode,
+    pub options: TFTPOptions,
 }
 
 impl WRQPacket {
     pub const OPCODE: u16 = 2;
 
     /// Constructs a TFTP WRQ packet in byte format
-    /// using the specified filename and mode.
-    pub fn create_wrq_packet(filename: &str, mode: &str) -> Vec<u8> {
+    /// using the specified filename, mode and options.
+    pub fn create_wrq_packet(filename: &str, mode: TransferMode, options: &TFTPOptions) -> Vec<u8> {
         let mut packet: Vec<u8> = Vec::new();
 
         // WRQ opcode = 2
@@ -110,65 +302,31 @@ impl WRQPacket {
         packet.push(0);
 
         // WRQ mode
-        packet.extend_from_slice(mode.as_bytes());
+        packet.extend_from_slice(mode.as_str().as_bytes());
 
         // WRQ null byte
         packet.push(0);
 
+        // WRQ options (RFC 2347)
+        options.write_into(&mut packet);
+
         packet
     }
 
     /// Parses a raw byte slice into a `WRQPacket`
-    pub fn parse(data: &[u8]) -> Result<Self, String> {
-        let opcode = match data.get(0..2) {
-            Some(v) => u16::from_be_bytes(v.try_into().unwrap()),
-            None => return Err(String::from("Invalid WRQ packet")),
-        };
-
-        if opcode != Self::OPCODE {
-            return Err(String::from("Invalid WRQ packet"));
-        }
-
-        let mut data_iterator = data
-            .iter()
-            .enumerate()
-            .skip_while(|(i, _)| *i == 0 || *i == 1); // Skip opcode bytes
-
-        let mut filename_bytes: Vec<u8> = Vec::new();
-
-        // Obtain the filename in byte format
-        loop {
-            match data_iterator.next() {
-                Some((_, byte)) => {
-                    if *byte == 0 {
-                        break;
-                    }
-                    filename_bytes.push(*byte);
-                }
-                None => return Err(String::from("Invalid WRQ packet")),
-            }
-        }
-
-        let mut mode_bytes: Vec<u8> = Vec::new();
+    /// (see [`Strictness::Lenient`]).
+    pub fn parse(data: &[u8]) -> Result<Self, PacketError> {
+        Self::parse_with(data, Strictness::default())
+    }
 
-        // Obtain the mode in byte format
-        loop {
-            match data_iterator.next() {
-                Some((_, byte)) => {
-                    if *byte == 0 {
-                        break;
-                    }
-                    mode_bytes.push(*byte);
-                }
-                None => return Err(String::from("Invalid WRQ packet")),
-            }
-        }
+    /// Parses a raw byte slice into a `WRQPacket`
+    /// with the specified strictness.
+    pub fn parse_with(data: &[u8], strictness: Strictness) -> Result<Self, PacketError> {
+        let mut reader = PacketReader::new(data, strictness);
 
-        let filename =
-            String::from_utf8(filename_bytes).map_err(|_| String::from("Invalid WRQ packet"))?;
-        let mode = String::from_utf8(mode_bytes).map_err(|_| String::from("Invalid WRQ packet"))?;
+        reader.opcode(Self::OPCODE)?;
 
-        Ok(Self { filename, mode })
+        RequestRef::read(&mut reader).map(|request| Self::from(&request))
     }
 }
 
@@ -182,12 +340,24 @@ pub struct DATAPacket {
 impl DATAPacket {
     pub const OPCODE: u16 = 3;
 
+    /// Block size defined by RFC 1350.
+    pub const DEFAULT_BLOCK_SIZE: usize = 512;
+
+    /// Smallest block size that can be negotiated (RFC 2348).
+    pub const MIN_BLOCK_SIZE: usize = 8;
+
+    /// Largest block size that can be negotiated (RFC 2348).
+    pub const MAX_BLOCK_SIZE: usize = 65464;
+
     /// Build a new `DATAPacket` instance.
-    pub fn build(block: u16, data: &[u8]) -> Result<Self, String> {
-        // The maximum length of a data in
-        // a TFTP DATA packet is equal to 512 bytes
-        if data.len() > 512 {
-            return Err(String::from("Invalid TFTP DATA packet"));
+    pub fn build(block: u16, data: &[u8]) -> Result<Self, PacketError> {
+        // The maximum length of a data in a TFTP DATA packet
+        // is equal to the largest negotiable block size
+        if data.len() > Self::MAX_BLOCK_SIZE {
+            return Err(PacketError::Oversized {
+                size: data.len() + 4,
+                max: Self::MAX_BLOCK_SIZE + 4,
+            });
         }
 
         Ok(Self {
@@ -197,51 +367,24 @@ impl DATAPacket {
     }
 
     /// Parses a raw byte slice into a `DATAPacket`.
-    pub fn parse(data: &[u8]) -> Result<Self, String> {
-        // The maximum length of a TFTP DATA packet
-        // is equal to 516 bytes:
-        // - Opcode: 2 bytes
-        // - Block number: 2 bytes
-        // - Data: 512 bytes
-        if data.len() > 516 {
-            return Err(String::from("Invalid TFTP DATA packet"));
-        }
-
-        let opcode = match data.get(0..2) {
-            Some(v) => u16::from_be_bytes(v.try_into().unwrap()),
-            None => return Err(String::from("Invalid TFTP DATA packet")),
-        };
-
-        if opcode != Self::OPCODE {
-            return Err(String::from("Invalid TFTP DATA packet"));
-        }
-
-        let block = match data.get(2..4) {
-            Some(v) => u16::from_be_bytes(v.try_into().unwrap()),
-            None => return Err(String::from("Invalid TFTP DATA packet")),
-        };
-
-        let mut data_packet = Vec::new();
+    pub fn parse(data: &[u8]) -> Result<Self, PacketError> {
+        Self::parse_with(data, Strictness::default())
+    }
 
-        for (i, byte) in data.iter().enumerate() {
-            // Skip Opcode and block bytes
-            if i < 4 {
-                continue;
-            }
+    /// Parses a raw byte slice into a `DATAPacket`
+    /// with the specified strictness.
+    pub fn parse_with(data: &[u8], strictness: Strictness) -> Result<Self, PacketError> {
+        let mut reader = PacketReader::new(data, strictness);
 
-            data_packet.push(*byte);
-        }
+        reader.opcode(Self::OPCODE)?;
 
-        Ok(Self {
-            block,
-            data: data_packet,
-        })
+        DATAPacketRef::read(&mut reader).map(|packet| Self::from(&packet))
     }
 
     /// Converts a `DATAPacket` into a TFTP DATA packet in byte format.
     pub fn as_bytes(&self) -> Vec<u8> {
         let mut packet: Vec<u8> = Vec::new();
-        
+
         // DATA opcode = 3
         packet.extend_from_slice(&Self::OPCODE.to_be_bytes());
 
@@ -254,7 +397,7 @@ impl DATAPacket {
         packet
     }
 
-    /// Get the data in a TFTP DATA packet (`DATAPacket`) 
+    /// Get the data in a TFTP DATA packet (`DATAPacket`)
     /// in byte format.
     pub fn get_data(&self) -> &[u8] {
         &self.data
@@ -262,7 +405,7 @@ impl DATAPacket {
 }
 
 /// Represents a TFTP ACK Packet.
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct ACKPacket {
     pub block: u16,
 }
@@ -270,21 +413,27 @@ pub struct ACKPacket {
 impl ACKPacket {
     pub const OPCODE: u16 = 4;
 
-    /// Parses a raw byte slice into a `ACKPacket`.
-    pub fn parse(data: &[u8]) -> Result<Self, String> {
-        let opcode = match data.get(0..2) {
-            Some(v) => u16::from_be_bytes(v.try_into().unwrap()),
-            None => return Err(String::from("Invalid TFTP ACK packet")),
-        };
+    /// Parses a raw byte slice into a `ACKPacket`
+    /// (see [`Strictness::Lenient`]).
+    pub fn parse(data: &[u8]) -> Result<Self, PacketError> {
+        Self::parse_with(data, Strictness::default())
+    }
 
-        if opcode != Self::OPCODE {
-            return Err(String::from("Invalid TFTP ACK packet"));
-        }
+    /// Parses a raw byte slice into a `ACKPacket`
+    /// with the specified strictness.
+    pub fn parse_with(data: &[u8], strictness: Strictness) -> Result<Self, PacketError> {
+        let mut reader = PacketReader::new(data, strictness);
 
-        let block = match data.get(2..4) {
-            Some(v) => u16::from_be_bytes(v.try_into().unwrap()),
-            None => return Err(String::from("Invalid TFTP ACK packet")),
-        };
+        reader.opcode(Self::OPCODE)?;
+
+        Self::read(&mut reader)
+    }
+
+    /// Reads the fields following the opcode.
+    pub(crate) fn read(reader: &mut PacketReader) -> Result<Self, PacketError> {
+        let block = reader.u16("block number")?;
+
+        reader.finish()?;
 
         Ok(Self { block })
     }
@@ -298,7 +447,46 @@ impl ACKPacket {
 
         // ACK block
         packet.extend_from_slice(&self.block.to_be_bytes());
-        
+
+        packet
+    }
+}
+
+/// Represents a TFTP OACK Packet (RFC 2347).
+#[derive(Debug)]
+pub struct OACKPacket {
+    pub options: TFTPOptions,
+}
+
+impl OACKPacket {
+    pub const OPCODE: u16 = 6;
+
+    /// Parses a raw byte slice into a `OACKPacket`
+    /// (see [`Strictness::Lenient`]).
+    pub fn parse(data: &[u8]) -> Result<Self, PacketError> {
+        Self::parse_with(data, Strictness::default())
+    }
+
+    /// Parses a raw byte slice into a `OACKPacket`
+    /// with the specified strictness.
+    pub fn parse_with(data: &[u8], strictness: Strictness) -> Result<Self, PacketError> {
+        let mut reader = PacketReader::new(data, strictness);
+
+        reader.opcode(Self::OPCODE)?;
+
+        OACKPacketRef::read(&mut reader).map(|packet| Self::from(&packet))
+    }
+
+    /// Converts a `OACKPacket` into a TFTP OACK packet in byte format.
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut packet: Vec<u8> = Vec::new();
+
+        // OACK opcode = 6
+        packet.extend_from_slice(&Self::OPCODE.to_be_bytes());
+
+        // OACK options
+        self.options.write_into(&mut packet);
+
         packet
     }
 }
@@ -314,6 +502,7 @@ pub enum ERRORPacket {
     UknownTransferID,
     FileAlreadyExists,
     NoSuchUser,
+    OptionNegotiationFailed,
 }
 
 impl ERRORPacket {
@@ -356,55 +545,40 @@ impl ERRORPacket {
             Self::UknownTransferID => Self::create_custom_error_packet(5, "Unknown transfer ID."),
             Self::FileAlreadyExists => Self::create_custom_error_packet(6, "File already exists."),
             Self::NoSuchUser => Self::create_custom_error_packet(7, "No such user."),
+            Self::OptionNegotiationFailed => {
+                Self::create_custom_error_packet(8, "Option negotiation failed.")
+            }
         }
     }
 
-    /// Parses a raw byte slice into a `ERRORPacket`.
-    pub fn parse(data: &[u8]) -> Result<Self, String> {
-        let opcode = match data.get(0..2) {
-            Some(v) => u16::from_be_bytes(v.try_into().unwrap()),
-            None => return Err(String::from("Invalid ERROR packet")),
-        };
-
-        if opcode != Self::OPCODE {
-            return Err(String::from("Invalid ERROR packet"));
-        }
-
-        let errcode = match data.get(2..4) {
-            Some(v) => u16::from_be_bytes(v.try_into().unwrap()),
-            None => return Err(String::from("Invalid ERROR packet")),
-        };
-
-        match errcode {
-            0 => {
-                let mut error_message_bytes: Vec<u8> = Vec::new();
+    /// Parses a raw byte slice into a `ERRORPacket`
+    /// (see [`Strictness::Lenient`]).
+    pub fn parse(data: &[u8]) -> Result<Self, PacketError> {
+        Self::parse_with(data, Strictness::default())
+    }
 
-                for (i, byte) in data.iter().enumerate() {
-                    // Skip Opcode and ErrCode bytes
-                    if i < 4 {
-                        continue;
-                    }
+    /// Parses a raw byte slice into a `ERRORPacket`
+    /// with the specified strictness.
+    pub fn parse_with(data: &[u8], strictness: Strictness) -> Result<Self, PacketError> {
+        let mut reader = PacketReader::new(data, strictness);
 
-                    if *byte == 0 {
-                        break;
-                    }
+        reader.opcode(Self::OPCODE)?;
 
-                    error_message_bytes.push(*byte);
-                }
+        ERRORPacketRef::read(&mut reader).map(|packet| Self::from(&packet))
+    }
 
-                match String::from_utf8(error_message_bytes) {
-                    Ok(error_message) => Ok(Self::NotDefined(error_message)),
-                    Err(_) => Err(String::from("Invalid ERROR packet")),
-                }
-            }
-            1 => Ok(Self::FileNotFound),
-            2 => Ok(Self::AccessViolation),
-            3 => Ok(Self::DiskFull),
-            4 => Ok(Self::IllegalTftpOperation),
-            5 => Ok(Self::UknownTransferID),
-            6 => Ok(Self::FileAlreadyExists),
-            7 => Ok(Self::NoSuchUser),
-            _ => Err(String::from("Invalid ERROR packet")),
+    /// Retrieve the error code from a TFTP ERROR packet.
+    pub fn get_error_code(&self) -> u16 {
+        match *self {
+            Self::NotDefined(_) => 0,
+            Self::FileNotFound => 1,
+            Self::AccessViolation => 2,
+            Self::DiskFull => 3,
+            Self::IllegalTftpOperation => 4,
+            Self::UknownTransferID => 5,
+            Self::FileAlreadyExists => 6,
+            Self::NoSuchUser => 7,
+            Self::OptionNegotiationFailed => 8,
         }
     }
 
@@ -419,12 +593,13 @@ impl ERRORPacket {
             Self::UknownTransferID => "Unknown transfer ID.".to_string(),
             Self::FileAlreadyExists => "File already exists.".to_string(),
             Self::NoSuchUser => "No such user.".to_string(),
+            Self::OptionNegotiationFailed => "Option negotiation failed.".to_string(),
         }
     }
 }
 
 /// Represents all TFTP packets
-/// decsribed in RFC 1350.
+/// decsribed in RFC 1350 and RFC 2347.
 #[derive(Debug)]
 pub enum TFTPPacket {
     RRQ(RRQPacket),
@@ -432,31 +607,319 @@ pub enum TFTPPacket {
     DATA(DATAPacket),
     ACK(ACKPacket),
     ERROR(ERRORPacket),
+    OACK(OACKPacket),
 }
 
 impl TFTPPacket {
     /// Parses a raw byte slice into a `TFTPPacket`
-    pub fn parse(data: &[u8]) -> Result<Self, String> {
-        if let Ok(rrq) = RRQPacket::parse(data) {
-            return Ok(Self::RRQ(rrq));
+    /// (see [`Strictness::Lenient`]).
+    pub fn parse(data: &[u8]) -> Result<Self, PacketError> {
+        Self::parse_with(data, Strictness::default())
+    }
+
+    /// Parses a raw byte slice into a `TFTPPacket`
+    /// of the type designated by its opcode, with
+    /// the specified strictness.
+    ///
+    /// Use [`PacketRef::parse_with`] to parse
+    /// a packet without copying its content.
+    pub fn parse_with(data: &[u8], strictness: Strictness) -> Result<Self, PacketError> {
+        PacketRef::parse_with(data, strictness).map(|packet| Self::from(&packet))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LAST: BlockNumber = BlockNumber(u16::MAX);
+
+    #[test]
+    fn next_block_number_rolls_over() {
+        assert_eq!(LAST.next(BlockRollover::ToZero), Some(BlockNumber(0)));
+        assert_eq!(LAST.next(BlockRollover::ToOne), Some(BlockNumber(1)));
+        assert_eq!(LAST.next(BlockRollover::Disabled), None);
+        assert_eq!(
+            BlockNumber(1).next(BlockRollover::Disabled),
+            Some(BlockNumber(2))
+        );
+    }
+
+    #[test]
+    fn offset_from_counts_across_rollover_to_zero() {
+        let rollover = BlockRollover::ToZero;
+
+        assert_eq!(BlockNumber(0).offset_from(LAST, rollover), Some(1));
+        assert_eq!(BlockNumber(3).offset_from(LAST, rollover), Some(4));
+        assert_eq!(LAST.offset_from(BlockNumber(0), rollover), Some(65535));
+    }
+
+    #[test]
+    fn offset_from_counts_across_rollover_to_one() {
+        let rollover = BlockRollover::ToOne;
+
+        assert_eq!(BlockNumber(1).offset_from(LAST, rollover), Some(1));
+        assert_eq!(BlockNumber(3).offset_from(LAST, rollover), Some(3));
+        assert_eq!(
+            BlockNumber(1).offset_from(BlockNumber::ZERO, rollover),
+            Some(1)
+        );
+        // The block number 0 only starts a transfer
+        assert_eq!(BlockNumber(0).offset_from(LAST, rollover), None);
+        assert_eq!(
+            BlockNumber::ZERO.offset_from(BlockNumber::ZERO, rollover),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn offset_from_stops_without_rollover() {
+        let rollover = BlockRollover::Disabled;
+
+        assert_eq!(BlockNumber(0).offset_from(LAST, rollover), None);
+        assert_eq!(LAST.offset_from(BlockNumber(1), rollover), Some(65534));
+    }
+
+    #[test]
+    fn offset_from_matches_next() {
+        for rollover in [BlockRollover::ToZero, BlockRollover::ToOne] {
+            let mut block = BlockNumber::ZERO;
+
+            for _ in 0..70000 {
+                let next = block.next(rollover).unwrap();
+
+                assert_eq!(next.offset_from(block, rollover), Some(1));
+                block = next;
+            }
         }
+    }
 
-        if let Ok(wrq) = WRQPacket::parse(data) {
-            return Ok(Self::WRQ(wrq));
+    #[test]
+    fn truncated_packets_report_the_missing_field() {
+        assert_eq!(
+            PacketRef::parse(&[0]).unwrap_err(),
+            PacketError::Truncated {
+                offset: 0,
+                field: "opcode"
+            }
+        );
+
+        for packet in [&[0, 3, 0][..], &[0, 4, 0]] {
+            let packet_error = PacketRef::parse(packet).unwrap_err();
+
+            assert_eq!(
+                packet_error,
+                PacketError::Truncated {
+                    offset: 2,
+                    field: "block number"
+                }
+            );
+            assert_eq!(
+                packet_error.to_string(),
+                "Truncated packet: missing block number at byte 2"
+            );
         }
 
-        if let Ok(data) = DATAPacket::parse(data) {
-            return Ok(Self::DATA(data));
+        // In lenient mode, the filename runs to the end of the packet
+        assert_eq!(
+            PacketRef::parse_with(b"\x00\x01file", Strictness::Lenient).unwrap_err(),
+            PacketError::Truncated {
+                offset: 6,
+                field: "mode"
+            }
+        );
+    }
+
+    #[test]
+    fn strict_parsing_requires_terminators() {
+        assert_eq!(
+            PacketRef::parse_with(b"\x00\x01file", Strictness::Strict).unwrap_err(),
+            PacketError::MissingTerminator {
+                offset: 2,
+                field: "filename"
+            }
+        );
+
+        let error = b"\x00\x05\x00\x01no such file";
+
+        assert_eq!(
+            ERRORPacket::parse_with(error, Strictness::Strict).unwrap_err(),
+            PacketError::MissingTerminator {
+                offset: 4,
+                field: "error message"
+            }
+        );
+        assert_eq!(
+            ERRORPacket::parse_with(error, Strictness::Lenient)
+                .unwrap()
+                .get_error_code(),
+            1
+        );
+    }
+
+    #[test]
+    fn opcodes_are_checked() {
+        assert_eq!(
+            PacketRef::parse(&[0, 9, 0, 1]).unwrap_err(),
+            PacketError::UnknownOpcode(9)
+        );
+        assert_eq!(
+            ACKPacket::parse(&[0, 9, 0, 1]).unwrap_err(),
+            PacketError::UnknownOpcode(9)
+        );
+        assert_eq!(
+            ACKPacket::parse(&[0, 3, 0, 1]).unwrap_err(),
+            PacketError::WrongOpcode {
+                expected: 4,
+                found: 3
+            }
+        );
+    }
+
+    #[test]
+    fn invalid_fields_report_their_offset() {
+        assert_eq!(
+            RRQPacket::parse(b"\x00\x01file\x00binary\x00").unwrap_err(),
+            PacketError::UnknownMode {
+                offset: 7,
+                mode: "binary".to_string()
+            }
+        );
+        assert_eq!(
+            RRQPacket::parse(b"\x00\x01\xfffile\x00octet\x00").unwrap_err(),
+            PacketError::InvalidUtf8 {
+                offset: 2,
+                field: "filename"
+            }
+        );
+        assert_eq!(
+            ERRORPacket::parse(&[0, 5, 0, 9, 0]).unwrap_err(),
+            PacketError::UnknownErrorCode(9)
+        );
+    }
+
+    #[test]
+    fn oversized_data_packets_are_refused() {
+        let mut data = vec![0_u8; DATAPacket::MAX_BLOCK_SIZE + 5];
+        data[1] = 3;
+
+        assert_eq!(
+            PacketRef::parse(&data).unwrap_err(),
+            PacketError::Oversized {
+                size: DATAPacket::MAX_BLOCK_SIZE + 5,
+                max: DATAPacket::MAX_BLOCK_SIZE + 4
+            }
+        );
+        assert!(PacketRef::parse(&data[..DATAPacket::MAX_BLOCK_SIZE + 4]).is_ok());
+    }
+
+    #[test]
+    fn strict_parsing_rejects_trailing_bytes() {
+        let ack = [0, 4, 0, 1, 0];
+
+        assert_eq!(
+            ACKPacket::parse_with(&ack, Strictness::Strict).unwrap_err(),
+            PacketError::TrailingBytes { offset: 4 }
+        );
+        assert_eq!(
+            ACKPacket::parse_with(&ack, Strictness::Lenient).unwrap(),
+            ACKPacket { block: 1 }
+        );
+    }
+
+    #[test]
+    fn strict_parsing_rejects_request_padding() {
+        let rrq = b"\x00\x01file\x00octet\x00\x00\x00";
+
+        assert_eq!(
+            RRQPacket::parse_with(rrq, Strictness::Strict).unwrap_err(),
+            PacketError::EmptyOptionName { offset: 13 }
+        );
+
+        let rrq = RRQPacket::parse_with(rrq, Strictness::Lenient).unwrap();
+
+        assert_eq!(rrq.filename, "file");
+        assert!(rrq.options.is_empty());
+    }
+
+    #[test]
+    fn mode_is_case_insensitive_in_both_strictnesses() {
+        for strictness in [Strictness::Strict, Strictness::Lenient] {
+            let rrq = RRQPacket::parse_with(b"\x00\x01file\x00NetASCII\x00", strictness).unwrap();
+
+            assert_eq!(rrq.mode, TransferMode::Netascii);
         }
+    }
+
+    fn options(pairs: &[(&str, &str)]) -> TFTPOptions {
+        let mut options = TFTPOptions::new();
 
-        if let Ok(ack) = ACKPacket::parse(data) {
-            return Ok(Self::ACK(ack));
+        for (name, value) in pairs {
+            options.insert(name, value);
         }
 
-        if let Ok(err) = ERRORPacket::parse(data) {
-            return Ok(Self::ERROR(err));
+        options
+    }
+
+    #[test]
+    fn options_ignore_the_case_of_names() {
+        let mut options = options(&[("BlkSize", "1024"), ("tsize", "0")]);
+
+        assert_eq!(options.get("BLKSIZE"), Some("1024"));
+
+        options.insert("TSIZE", "42");
+
+        assert_eq!(
+            options.iter().collect::<Vec<_>>(),
+            [("blksize", "1024"), ("tsize", "42")]
+        );
+    }
+
+    #[test]
+    fn oack_round_trips() {
+        let oack = OACKPacket {
+            options: options(&[("blksize", "1024"), ("tsize", "0")]),
+        };
+        let bytes = oack.as_bytes();
+
+        assert_eq!(bytes, b"\x00\x06blksize\x001024\x00tsize\x000\x00");
+
+        for strictness in [Strictness::Strict, Strictness::Lenient] {
+            assert_eq!(
+                OACKPacket::parse_with(&bytes, strictness).unwrap().options,
+                oack.options
+            );
         }
+    }
+
+    #[test]
+    fn oack_without_options_round_trips() {
+        let oack = OACKPacket {
+            options: TFTPOptions::new(),
+        };
+
+        assert_eq!(oack.as_bytes(), [0, 6]);
+        assert!(OACKPacket::parse_with(&oack.as_bytes(), Strictness::Strict)
+            .unwrap()
+            .options
+            .is_empty());
+    }
+
+    #[test]
+    fn request_options_round_trip() {
+        let options = options(&[("blksize", "1428"), ("windowsize", "16"), ("timeout", "3")]);
+
+        let rrq = RRQPacket::create_rrq_packet("dir/file", TransferMode::Octet, &options);
+        let rrq = RRQPacket::parse_with(&rrq, Strictness::Strict).unwrap();
+
+        assert_eq!(rrq.filename, "dir/file");
+        assert_eq!(rrq.mode, TransferMode::Octet);
+        assert_eq!(rrq.options, options);
+
+        let wrq = WRQPacket::create_wrq_packet("file", TransferMode::Netascii, &options);
+        let wrq = WRQPacket::parse_with(&wrq, Strictness::Strict).unwrap();
 
-        Err(String::from("Invalid TFTP Packet"))
+        assert_eq!(wrq.mode, TransferMode::Netascii);
+        assert_eq!(wrq.options, options);
     }
 }
@@ -0,0 +1,289 @@
+//! Translation between local text files and
+//! the netascii transfer mode (RFC 764, RFC 1350).
+//!
+//! Local lines end with LF, which netascii sends as CR LF,
+//! while a bare CR is sent as CR NUL.
+
+use std::io::{self, Read, Write};
+
+const CR: u8 = b'\r';
+const LF: u8 = b'\n';
+const NUL: u8 = 0;
+
+/// Reads a local text file as netascii.
+///
+/// The DATA packets are cut from the translated stream, so
+/// a CR LF pair may be split across two blocks.
+#[derive(Debug)]
+pub struct NetasciiReader<R> {
+    inner: R,
+    raw: Box<[u8]>,
+    raw_pos: usize,
+    raw_len: usize,
+    // Second byte of a pair which did not fit
+    pending: Option<u8>,
+}
+
+impl<R: Read> NetasciiReader<R> {
+    /// Constructs a new `NetasciiReader` reading from `inner`.
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            raw: vec![0_u8; 8192].into_boxed_slice(),
+            raw_pos: 0,
+            raw_len: 0,
+            pending: None,
+        }
+    }
+}
+
+impl<R: Read> Read for NetasciiReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut written = 0;
+
+        if let Some(byte) = self.pending {
+            match buf.first_mut() {
+                Some(first) => *first = byte,
+                None => return Ok(0),
+            }
+
+            self.pending = None;
+            written = 1;
+        }
+
+        while written < buf.len() {
+            if self.raw_pos == self.raw_len {
+                // Only wait for more data when
+                // nothing can be returned yet
+                if written > 0 {
+                    break;
+                }
+
+                self.raw_len = self.inner.read(&mut self.raw)?;
+                self.raw_pos = 0;
+
+                if self.raw_len == 0 {
+                    break;
+                }
+            }
+
+            let byte = self.raw[self.raw_pos];
+            self.raw_pos += 1;
+
+            let second = match byte {
+                LF => Some(LF),
+                CR => Some(NUL),
+                _ => None,
+            };
+
+            match second {
+                Some(second) => {
+                    buf[written] = CR;
+                    written += 1;
+
+                    match buf.get_mut(written) {
+                        Some(next) => {
+                            *next = second;
+                            written += 1;
+                        }
+                        None => self.pending = Some(second),
+                    }
+                }
+                None => {
+                    buf[written] = byte;
+                    written += 1;
+                }
+            }
+        }
+
+        Ok(written)
+    }
+}
+
+/// Writes netascii data as a local text file.
+///
+/// A CR ending a block is held until the next block tells
+/// whether it starts a CR LF or a CR NUL pair, so `finish`
+/// must be called once the last block has been written.
+#[derive(Debug)]
+pub struct NetasciiWriter<W> {
+    inner: W,
+    pending_cr: bool,
+}
+
+impl<W: Write> NetasciiWriter<W> {
+    /// Constructs a new `NetasciiWriter` writing into `inner`.
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            pending_cr: false,
+        }
+    }
+
+    /// Writes a CR left at the end of the data,
+    /// and returns the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.pending_cr {
+            self.inner.write_all(&[CR])?;
+        }
+
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for NetasciiWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut translated = Vec::with_capacity(buf.len() + 1);
+
+        for &byte in buf {
+            match (self.pending_cr, byte) {
+                (true, LF) => translated.push(LF),
+                (true, NUL) => translated.push(CR),
+                (true, CR) => translated.push(CR),
+                // Not netascii, the CR is kept as is
+                (true, _) => translated.extend_from_slice(&[CR, byte]),
+                (false, CR) => {}
+                (false, _) => translated.push(byte),
+            }
+
+            // A CR is held until the next byte
+            self.pending_cr = byte == CR;
+        }
+
+        self.inner.write_all(&translated)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Returns the size of a local text file once translated
+/// into netascii, reading it from `reader`.
+pub fn netascii_size(reader: &mut impl Read) -> io::Result<u64> {
+    let mut buffer = [0_u8; 8192];
+    let mut size = 0;
+
+    loop {
+        let bytes = match reader.read(&mut buffer) {
+            Ok(0) => return Ok(size),
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+
+        // Each LF and CR is sent along with another byte
+        let pairs = buffer[..bytes]
+            .iter()
+            .filter(|&&byte| byte == LF || byte == CR)
+            .count();
+
+        size += (bytes + pairs) as u64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Local text with every case of the translation:
+    /// LF, CR LF, bare CR, consecutive CRs and a trailing CR.
+    const TEXT: &[u8] = b"line 1\nline 2\r\nbare\rcr\r\r\nend\r";
+
+    /// The same text in netascii.
+    const NETASCII: &[u8] = b"line 1\r\nline 2\r\0\r\nbare\r\0cr\r\0\r\0\r\nend\r\0";
+
+    /// Reads `text` as netascii in blocks of `block_size` bytes,
+    /// filling each block as a transfer does.
+    fn encode(text: &[u8], block_size: usize) -> Vec<Vec<u8>> {
+        let mut reader = NetasciiReader::new(text);
+        let mut blocks = Vec::new();
+
+        loop {
+            let mut block = vec![0_u8; block_size];
+            let mut read_bytes = 0;
+
+            while read_bytes < block_size {
+                match reader.read(&mut block[read_bytes..]).unwrap() {
+                    0 => break,
+                    bytes => read_bytes += bytes,
+                }
+            }
+
+            block.truncate(read_bytes);
+            blocks.push(block);
+
+            if read_bytes < block_size {
+                return blocks;
+            }
+        }
+    }
+
+    /// Writes the netascii `blocks` as local text.
+    fn decode<'a>(blocks: impl IntoIterator<Item = &'a [u8]>) -> Vec<u8> {
+        let mut writer = NetasciiWriter::new(Vec::new());
+
+        for block in blocks {
+            writer.write_all(block).unwrap();
+        }
+
+        writer.finish().unwrap()
+    }
+
+    #[test]
+    fn encodes_line_endings_and_bare_cr() {
+        assert_eq!(encode(TEXT, 1024).concat(), NETASCII);
+    }
+
+    #[test]
+    fn encodes_pairs_split_at_every_block_boundary() {
+        for block_size in 1..=NETASCII.len() + 1 {
+            let blocks = encode(TEXT, block_size);
+
+            assert_eq!(blocks.concat(), NETASCII, "block size {}", block_size);
+            assert!(blocks[..blocks.len() - 1]
+                .iter()
+                .all(|block| block.len() == block_size));
+        }
+    }
+
+    #[test]
+    fn decodes_pairs_split_at_every_offset() {
+        for split in 0..=NETASCII.len() {
+            let (first, second) = NETASCII.split_at(split);
+
+            assert_eq!(decode([first, second]), TEXT, "split at {}", split);
+        }
+    }
+
+    #[test]
+    fn decodes_blocks_of_every_size() {
+        for block_size in 1..=NETASCII.len() {
+            assert_eq!(
+                decode(NETASCII.chunks(block_size)),
+                TEXT,
+                "block size {}",
+                block_size
+            );
+        }
+    }
+
+    #[test]
+    fn keeps_a_trailing_bare_cr() {
+        assert_eq!(decode([&b"end\r"[..]]), b"end\r");
+        assert_eq!(decode([&b"end"[..], &b"\r"[..]]), b"end\r");
+    }
+
+    #[test]
+    fn size_matches_the_encoded_data() {
+        for text in [TEXT, b"", b"\r", b"\n", b"no line ending", &[CR; 10000]] {
+            let size = netascii_size(&mut &text[..]).unwrap();
+            let encoded = encode(text, 512).concat();
+
+            assert_eq!(size, encoded.len() as u64);
+            assert_eq!(decode([&encoded[..]]), text);
+        }
+    }
+}
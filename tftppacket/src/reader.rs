@@ -0,0 +1,110 @@
+use crate::{PacketError, Strictness};
+
+/// Reads the fields of a TFTP packet in order,
+/// keeping the byte offset of the next field.
+pub(crate) struct PacketReader<'a> {
+    data: &'a [u8],
+    offset: usize,
+    strictness: Strictness,
+}
+
+impl<'a> PacketReader<'a> {
+    /// Constructs a `PacketReader` positioned on the opcode of `data`.
+    pub(crate) fn new(data: &'a [u8], strictness: Strictness) -> Self {
+        Self {
+            data,
+            offset: 0,
+            strictness,
+        }
+    }
+
+    pub(crate) fn strictness(&self) -> Strictness {
+        self.strictness
+    }
+
+    /// Returns the offset of the next field.
+    pub(crate) fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Returns the length of the whole packet.
+    pub(crate) fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Returns `true` if all the bytes of the packet have been read.
+    pub(crate) fn is_at_end(&self) -> bool {
+        self.offset >= self.data.len()
+    }
+
+    /// Reads the opcode, which must be `expected`.
+    pub(crate) fn opcode(&mut self, expected: u16) -> Result<(), PacketError> {
+        match self.u16("opcode")? {
+            found if found == expected => Ok(()),
+            found @ 1..=6 => Err(PacketError::WrongOpcode { expected, found }),
+            found => Err(PacketError::UnknownOpcode(found)),
+        }
+    }
+
+    /// Reads the 16-bit `field`.
+    pub(crate) fn u16(&mut self, field: &'static str) -> Result<u16, PacketError> {
+        let offset = self.offset;
+
+        match self.data.get(offset..offset + 2) {
+            Some(bytes) => {
+                self.offset += 2;
+                Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+            }
+            None => Err(PacketError::Truncated { offset, field }),
+        }
+    }
+
+    /// Reads the null-terminated string `field`.
+    ///
+    /// In lenient mode, a string running to the
+    /// end of the packet may lack its null byte.
+    pub(crate) fn string(&mut self, field: &'static str) -> Result<&'a str, PacketError> {
+        let offset = self.offset;
+
+        if self.is_at_end() {
+            return Err(PacketError::Truncated { offset, field });
+        }
+
+        let bytes = &self.data[offset..];
+
+        let (length, next_offset) = match bytes.iter().position(|byte| *byte == 0) {
+            Some(length) => (length, offset + length + 1),
+            None if self.strictness == Strictness::Lenient => (bytes.len(), self.data.len()),
+            None => return Err(PacketError::MissingTerminator { offset, field }),
+        };
+
+        let string = std::str::from_utf8(&bytes[..length])
+            .map_err(|_| PacketError::InvalidUtf8 { offset, field })?;
+
+        self.offset = next_offset;
+
+        Ok(string)
+    }
+
+    /// Reads all the remaining bytes of the packet.
+    pub(crate) fn rest(&mut self) -> &'a [u8] {
+        let bytes = self.data.get(self.offset..).unwrap_or_default();
+        self.offset = self.data.len();
+        bytes
+    }
+
+    /// Ends the reading of the packet, which must have no
+    /// bytes left in strict mode. In lenient mode, the
+    /// remaining bytes are ignored.
+    pub(crate) fn finish(&mut self) -> Result<(), PacketError> {
+        if !self.is_at_end() && self.strictness == Strictness::Strict {
+            return Err(PacketError::TrailingBytes {
+                offset: self.offset,
+            });
+        }
+
+        self.offset = self.data.len();
+
+        Ok(())
+    }
+}
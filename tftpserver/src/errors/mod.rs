@@ -0,0 +1,38 @@
+use std::io;
+
+use tftppacket::ERRORPacket;
+
+/// Determines how much detail the ERROR packets
+/// sent to clients give about server failures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorVerbosity {
+    /// Only the standard error messages are sent, so that
+    /// no detail of the server leaks to the clients.
+    Minimal,
+    /// The description of the failure is sent along.
+    Detailed,
+}
+
+/// Converts a filesystem error into the ERROR packet of the
+/// matching TFTP error code, or an undefined error (code 0).
+pub fn io_error_packet(e: &io::Error, error_verbosity: ErrorVerbosity) -> ERRORPacket {
+    match e.kind() {
+        io::ErrorKind::NotFound | io::ErrorKind::NotADirectory => ERRORPacket::FileNotFound,
+        io::ErrorKind::PermissionDenied
+        | io::ErrorKind::ReadOnlyFilesystem
+        | io::ErrorKind::IsADirectory => ERRORPacket::AccessViolation,
+        io::ErrorKind::AlreadyExists => ERRORPacket::FileAlreadyExists,
+        // ENOSPC, EDQUOT and EFBIG
+        io::ErrorKind::StorageFull | io::ErrorKind::QuotaExceeded | io::ErrorKind::FileTooLarge => {
+            ERRORPacket::DiskFull
+        }
+        _ => match error_verbosity {
+            ErrorVerbosity::Minimal => {
+                ERRORPacket::NotDefined(String::from("An error occurs on the server"))
+            }
+            ErrorVerbosity::Detailed => {
+                ERRORPacket::NotDefined(format!("An error occurs on the server: {}", e))
+            }
+        },
+    }
+}
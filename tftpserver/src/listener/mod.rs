@@ -0,0 +1,123 @@
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket},
+};
+
+use socket2::{Domain, Protocol, Socket, Type};
+
+mod pktinfo;
+
+/// UDP socket receiving the RRQ/WRQ packets sent to a listen address.
+#[derive(Debug)]
+pub struct Listener {
+    socket: UdpSocket,
+}
+
+impl Listener {
+    /// Binds a new `Listener` to `listen_addr`.
+    ///
+    /// With `dual_stack`, the IPv6 wildcard address also accepts
+    /// IPv4 clients, as IPv4-mapped IPv6 addresses.
+    pub fn bind(listen_addr: SocketAddr, dual_stack: bool) -> io::Result<Self> {
+        let socket = Socket::new(
+            Domain::for_address(listen_addr),
+            Type::DGRAM,
+            Some(Protocol::UDP),
+        )?;
+
+        if listen_addr.is_ipv6() {
+            socket.set_only_v6(!dual_stack)?;
+        }
+
+        socket.bind(&listen_addr.into())?;
+
+        // Sessions answer from the address the request
+        // was sent to, even on a wildcard address
+        pktinfo::enable(&socket, listen_addr.is_ipv6())?;
+
+        Ok(Self {
+            socket: socket.into(),
+        })
+    }
+
+    /// Returns the address the listener is bound to.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// Receives a request into `buffer`, returning its length,
+    /// the address of the client and the local address the
+    /// request was sent to (the address of the listener
+    /// when the system does not report it).
+    ///
+    /// IPv4 addresses of a dual-stack listener
+    /// are reported as IPv4 addresses.
+    pub fn recv_request(&self, buffer: &mut [u8]) -> io::Result<(usize, SocketAddr, SocketAddr)> {
+        let (bytes, client_addr, local_addr) = pktinfo::recv_from(&self.socket, buffer)?;
+
+        let local_addr = match local_addr {
+            Some(local_addr) => local_addr,
+            None => self.socket.local_addr()?,
+        };
+
+        Ok((bytes, unmap_ipv4(client_addr), unmap_ipv4(local_addr)))
+    }
+
+    /// Sends a packet to a client, such as an ERROR
+    /// packet answering a request that is refused.
+    pub fn send_to(&self, buffer: &[u8], client_addr: SocketAddr) -> io::Result<usize> {
+        // IPv4 clients of a dual-stack listener
+        // are reached through their mapped address
+        let client_addr = match (client_addr, self.socket.local_addr()?) {
+            (SocketAddr::V4(addr), SocketAddr::V6(_)) => {
+                SocketAddr::new(IpAddr::V6(addr.ip().to_ipv6_mapped()), addr.port())
+            }
+            _ => client_addr,
+        };
+
+        self.socket.send_to(buffer, client_addr)
+    }
+}
+
+/// Binds the socket of a new session with `client_addr` to
+/// `local_addr`, the address the request was sent to, on a
+/// port chosen by the system (the TID of the server).
+///
+/// The unspecified address of the family of the client is
+/// used instead when `local_addr` cannot be bound, such as
+/// a wildcard, broadcast or multicast address.
+pub fn bind_session_socket(
+    client_addr: SocketAddr,
+    local_addr: SocketAddr,
+) -> io::Result<UdpSocket> {
+    let bindable = match (client_addr, local_addr) {
+        (SocketAddr::V4(_), SocketAddr::V4(addr)) => {
+            !(addr.ip().is_unspecified() || addr.ip().is_broadcast() || addr.ip().is_multicast())
+        }
+        (SocketAddr::V6(_), SocketAddr::V6(addr)) => {
+            !(addr.ip().is_unspecified() || addr.ip().is_multicast())
+        }
+        _ => false,
+    };
+
+    let mut local_addr = match (bindable, client_addr) {
+        (true, _) => local_addr,
+        (false, SocketAddr::V4(_)) => SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
+        (false, SocketAddr::V6(_)) => SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0),
+    };
+
+    local_addr.set_port(0);
+
+    UdpSocket::bind(local_addr)
+}
+
+/// Converts an IPv4-mapped IPv6 address into an IPv4 address.
+fn unmap_ipv4(addr: SocketAddr) -> SocketAddr {
+    match addr {
+        SocketAddr::V6(v6_addr) => match v6_addr.ip().to_ipv4_mapped() {
+            Some(ip) => SocketAddr::new(IpAddr::V4(ip), v6_addr.port()),
+            None => addr,
+        },
+        SocketAddr::V4(_) => addr,
+    }
+}
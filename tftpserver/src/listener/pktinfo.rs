@@ -0,0 +1,146 @@
+//! Destination address of the received requests
+//! (`IP_PKTINFO` and `IPV6_RECVPKTINFO`).
+
+use std::{
+    io,
+    net::{SocketAddr, UdpSocket},
+};
+
+use socket2::Socket;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use std::{
+    mem,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddrV6},
+    os::fd::AsRawFd,
+    ptr,
+};
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use socket2::SockAddr;
+
+/// Asks the system to report the destination
+/// address of each packet received by `socket`.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn enable(socket: &Socket, ipv6: bool) -> io::Result<()> {
+    let (level, name) = match ipv6 {
+        true => (libc::IPPROTO_IPV6, libc::IPV6_RECVPKTINFO),
+        false => (libc::IPPROTO_IP, libc::IP_PKTINFO),
+    };
+
+    let enabled: libc::c_int = 1;
+
+    // SAFETY: `enabled` is a valid `c_int`
+    // whose size is given to `setsockopt`
+    let result = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            level,
+            name,
+            &enabled as *const libc::c_int as *const libc::c_void,
+            mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+
+    match result {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+/// Receives a packet into `buffer`, returning its length, its
+/// source address and, when known, its destination address.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn recv_from(
+    socket: &UdpSocket,
+    buffer: &mut [u8],
+) -> io::Result<(usize, SocketAddr, Option<SocketAddr>)> {
+    // SAFETY: zeroed structures are valid values for these C types
+    let mut source: libc::sockaddr_storage = unsafe { mem::zeroed() };
+    let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+
+    // Aligned for the `cmsghdr` structures
+    let mut control = [0_u64; 16];
+
+    let mut iov = libc::iovec {
+        iov_base: buffer.as_mut_ptr() as *mut libc::c_void,
+        iov_len: buffer.len(),
+    };
+
+    msg.msg_name = &mut source as *mut libc::sockaddr_storage as *mut libc::c_void;
+    msg.msg_namelen = mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+    msg.msg_controllen = mem::size_of_val(&control) as _;
+
+    // SAFETY: every pointer of `msg` refers to a
+    // live buffer of the length given alongside it
+    let bytes = unsafe { libc::recvmsg(socket.as_raw_fd(), &mut msg, 0) };
+
+    if bytes < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    // SAFETY: `recvmsg` filled `source` with an address of `msg_namelen` bytes
+    let source_addr = unsafe { SockAddr::new(source, msg.msg_namelen) }
+        .as_socket()
+        .ok_or(io::Error::from(io::ErrorKind::InvalidData))?;
+
+    let mut destination_addr = None;
+
+    // SAFETY: the control messages are walked with the `CMSG_*`
+    // macros over the `msg_controllen` bytes filled by `recvmsg`,
+    // and their data is read unaligned into the matching C type
+    unsafe {
+        let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+
+        while !cmsg.is_null() {
+            let data = libc::CMSG_DATA(cmsg);
+
+            match ((*cmsg).cmsg_level, (*cmsg).cmsg_type) {
+                (libc::IPPROTO_IP, libc::IP_PKTINFO) => {
+                    let info = ptr::read_unaligned(data as *const libc::in_pktinfo);
+                    let ip = Ipv4Addr::from(u32::from_be(info.ipi_spec_dst.s_addr));
+
+                    destination_addr = Some(SocketAddr::new(IpAddr::V4(ip), 0));
+                }
+                (libc::IPPROTO_IPV6, libc::IPV6_PKTINFO) => {
+                    let info = ptr::read_unaligned(data as *const libc::in6_pktinfo);
+                    let ip = Ipv6Addr::from(info.ipi6_addr.s6_addr);
+
+                    destination_addr = Some(SocketAddr::V6(SocketAddrV6::new(
+                        ip,
+                        0,
+                        0,
+                        info.ipi6_ifindex,
+                    )));
+                }
+                _ => {}
+            }
+
+            cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+        }
+    }
+
+    Ok((bytes as usize, source_addr, destination_addr))
+}
+
+/// The destination address of the packets
+/// is not reported on this platform.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub fn enable(_socket: &Socket, _ipv6: bool) -> io::Result<()> {
+    Ok(())
+}
+
+/// Receives a packet into `buffer`, returning its length and
+/// its source address (the destination address is unknown).
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub fn recv_from(
+    socket: &UdpSocket,
+    buffer: &mut [u8],
+) -> io::Result<(usize, SocketAddr, Option<SocketAddr>)> {
+    let (bytes, source_addr) = socket.recv_from(buffer)?;
+
+    Ok((bytes, source_addr, None))
+}
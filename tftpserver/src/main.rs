@@ -1,25 +1,103 @@
 use std::{
-    env,
-    fs::{File, OpenOptions},
-    io::{Read, Write},
-    net::{SocketAddr, UdpSocket},
+    io::{self, Read, Seek},
+    net::SocketAddr,
     path::Path,
+    sync::Arc,
+    thread,
+    time::Duration,
 };
 
-use rand::Rng;
-use tftppacket::{ACKPacket, DATAPacket, ERRORPacket, RRQPacket, TFTPPacket, WRQPacket};
+use errors::{io_error_packet, ErrorVerbosity};
+use listener::{bind_session_socket, Listener};
+use pool::{SessionLimits, SessionRefusal, WorkerPool};
+use resolver::{open_read_path, resolve_read_path, resolve_write_path, PathError};
+use tftppacket::{
+    netascii_size, ACKPacket, DATAPacket, ERRORPacket, NetasciiReader, NetasciiWriter, OACKPacket,
+    RRQPacket, TFTPOptions, TFTPPacket, TransferMode, WRQPacket,
+};
+use tftptransfer::{
+    acknowledge_file, receive_file, send_file, send_oack, too_large_error, RttEstimator,
+    TransferError, TransferOptions, TransferSocket,
+};
+use upload::PartialUpload;
+use utils::ServerArgs;
+
+mod errors;
+mod listener;
+mod pool;
+mod resolver;
+mod upload;
+mod utils;
 
 fn main() -> Result<(), String> {
-    let server_socket = UdpSocket::bind("0.0.0.0:69")
-        .map_err(|e| format!("Unable to initialize a UDP server socket: {}", e))?;
+    let server_args = Arc::new(ServerArgs::build()?);
+
+    // Without any IPv4 listen address, an IPv6
+    // wildcard address also serves IPv4 clients
+    let dual_stack = !server_args.listen_addrs.iter().any(SocketAddr::is_ipv4);
+
+    let listeners = server_args
+        .listen_addrs
+        .iter()
+        .map(|&listen_addr| {
+            Listener::bind(listen_addr, dual_stack).map_err(|e| {
+                format!(
+                    "Unable to initialize a UDP server socket on {}: {}",
+                    listen_addr, e
+                )
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    // Each session runs on a worker, so
+    // that clients are served concurrently
+    let worker_pool = Arc::new(WorkerPool::new(server_args.max_sessions)?);
+    let session_limits = SessionLimits::new(
+        server_args.max_sessions,
+        server_args.max_sessions_per_client,
+    );
 
     println!("The TFTP server is running successfully...");
 
+    for listener in listeners.iter() {
+        if let Ok(listen_addr) = listener.local_addr() {
+            println!("Listening on {}", listen_addr);
+        }
+    }
+
+    println!("Serving files from {}", server_args.read_dir.display());
+    println!("Storing uploads in {}", server_args.upload_dir.display());
+
+    // Each listen address is served by its own thread
+    let listener_threads = listeners
+        .into_iter()
+        .map(|listener| {
+            let server_args = Arc::clone(&server_args);
+            let worker_pool = Arc::clone(&worker_pool);
+            let session_limits = Arc::clone(&session_limits);
+
+            thread::spawn(move || serve(listener, server_args, worker_pool, session_limits))
+        })
+        .collect::<Vec<_>>();
+
+    for listener_thread in listener_threads {
+        let _ = listener_thread.join();
+    }
+
+    Ok(())
+}
+
+fn serve(
+    listener: Listener,
+    server_args: Arc<ServerArgs>,
+    worker_pool: Arc<WorkerPool>,
+    session_limits: Arc<SessionLimits>,
+) {
     loop {
         // Create a buffer to store a TFTP request
         let mut request = [0_u8; 512];
 
-        let (req_bytes, client_addr) = match server_socket.recv_from(&mut request) {
+        let (req_bytes, client_addr, local_addr) = match listener.recv_request(&mut request) {
             Ok(recv_info) => recv_info,
             Err(e) => {
                 eprintln!("Unable to receive a TFTP request packet: {}", e);
@@ -27,252 +105,496 @@ fn main() -> Result<(), String> {
             }
         };
 
-        match TFTPPacket::parse(&request[..req_bytes]) {
-            Ok(TFTPPacket::RRQ(rrq)) => client_read_from_server(rrq, client_addr),
-            Ok(TFTPPacket::WRQ(wrq)) => client_write_to_server(wrq, client_addr),
-            _ => {
+        let request = match TFTPPacket::parse_with(&request[..req_bytes], server_args.strictness) {
+            Ok(request @ (TFTPPacket::RRQ(_) | TFTPPacket::WRQ(_))) => request,
+            Ok(_) => {
                 let err_packet = ERRORPacket::IllegalTftpOperation;
-                server_socket.send_to(&err_packet.as_bytes(), client_addr);
+                let _ = listener.send_to(&err_packet.as_bytes(), client_addr);
                 eprintln!("Error: {}", err_packet.get_error_message());
                 continue;
             }
-        }
+            Err(e) => {
+                let err_packet = ERRORPacket::IllegalTftpOperation;
+                let _ = listener.send_to(&err_packet.as_bytes(), client_addr);
+                eprintln!("Error: Malformed request from {}: {}", client_addr, e);
+                continue;
+            }
+        };
+
+        let session_permit = match session_limits.try_acquire(client_addr) {
+            Ok(permit) => permit,
+            Err(SessionRefusal::Busy) => {
+                let err_packet = ERRORPacket::NotDefined("Server busy".to_string());
+                let _ = listener.send_to(&err_packet.as_bytes(), client_addr);
+                eprintln!("Error: Server busy, request from {} refused", client_addr);
+                continue;
+            }
+            // The running session answers the client
+            Err(SessionRefusal::Duplicate) => {
+                eprintln!("Warning: duplicate request from {} ignored", client_addr);
+                continue;
+            }
+        };
+
+        let server_args = Arc::clone(&server_args);
+
+        worker_pool.execute(move || {
+            // The session is released when the transfer ends
+            let _session_permit = session_permit;
+
+            match request {
+                TFTPPacket::RRQ(rrq) => {
+                    client_read_from_server(rrq, client_addr, local_addr, &server_args)
+                }
+                TFTPPacket::WRQ(wrq) => {
+                    client_write_to_server(wrq, client_addr, local_addr, &server_args)
+                }
+                _ => {}
+            }
+        });
     }
 }
 
-fn client_read_from_server(rrq: RRQPacket, client_addr: SocketAddr) {
-    let server_socket = loop {
-        let server_tid: u16 = rand::thread_rng().gen_range(0..65535);
-
-        match UdpSocket::bind(format!("0.0.0.0:{}", server_tid)) {
-            Ok(socket) => break socket,
-            Err(_) => continue,
+fn client_read_from_server(
+    rrq: RRQPacket,
+    client_addr: SocketAddr,
+    local_addr: SocketAddr,
+    server_args: &ServerArgs,
+) {
+    let socket = match bind_session_socket(client_addr, local_addr) {
+        Ok(socket) => socket,
+        Err(e) => {
+            eprintln!("Error: Unable to initialize a session socket: {}", e);
+            return;
         }
     };
 
-    // Ensure connection with the client
-    if let Err(e) = server_socket.connect(client_addr) {
-        eprintln!("Error: {}", e);
-        return;
-    }
-
-    if rrq.mode.to_lowercase() != "octet" {
-        let err_packet =
-            ERRORPacket::NotDefined("The server supports only the 'octet' mode".to_string());
-        server_socket.send(&err_packet.as_bytes());
-        eprintln!("Error: {}", err_packet.get_error_message());
-        return;
-    }
+    // Packets from any other source than
+    // the client are rejected (RFC 1350)
+    let server_socket = TransferSocket::new(socket, client_addr, warn_unknown_tid);
+
+    // Text files are translated in the
+    // netascii mode (RFC 764, RFC 1350)
+    let netascii = match rrq.mode {
+        TransferMode::Netascii => true,
+        TransferMode::Octet => false,
+        TransferMode::Mail => {
+            let err_packet = ERRORPacket::NotDefined(
+                "The server supports only the 'netascii' and 'octet' modes".to_string(),
+            );
+            let _ = server_socket.send(&err_packet.as_bytes());
+            eprintln!("Error: {}", err_packet.get_error_message());
+            return;
+        }
+    };
 
-    let bin_dir = match env::current_exe() {
-        Ok(bin_path) => bin_path.parent().unwrap().to_owned(),
+    let file_path = match resolve_read_path(
+        &server_args.read_dir,
+        &rrq.filename,
+        server_args.symlink_policy,
+    ) {
+        Ok(path) => path,
         Err(e) => {
-            let err_packet =
-                ERRORPacket::NotDefined(format!("An error occurs on the server: {}", e));
-            server_socket.send(&err_packet.as_bytes());
-            eprintln!("Error: {}", e);
+            let _ =
+                server_socket.send(&path_error_packet(&e, server_args.error_verbosity).as_bytes());
+            eprintln!("Error: {} [{:?}]", e, rrq.filename);
             return;
         }
     };
 
-    let mut file = match File::open(bin_dir.join(&rrq.filename)) {
+    let mut file = match open_read_path(&file_path, server_args.symlink_policy) {
         Ok(f) => f,
         Err(e) => {
-            let err_packet =
-                ERRORPacket::NotDefined(format!("An error occurs on the server: {}", e));
-            server_socket.send(&err_packet.as_bytes());
+            let err_packet = io_error_packet(&e, server_args.error_verbosity);
+            let _ = server_socket.send(&err_packet.as_bytes());
             eprintln!("Error: {}", e);
             return;
         }
     };
 
-    let mut current_block_number = 1;
-
-    loop {
-        let mut data_buffer = [0_u8; 512];
+    // The size of the file is reported
+    // with the tsize option (RFC 2349)
+    let file_size = match file.metadata() {
+        Ok(metadata) if metadata.is_file() => metadata.len(),
+        // Directories, devices and FIFOs are not served
+        Ok(_) => {
+            let err_packet = ERRORPacket::AccessViolation;
+            let _ = server_socket.send(&err_packet.as_bytes());
+            eprintln!("Error: Not a regular file [{:?}]", rrq.filename);
+            return;
+        }
+        Err(e) => {
+            let err_packet = io_error_packet(&e, server_args.error_verbosity);
+            let _ = server_socket.send(&err_packet.as_bytes());
+            eprintln!("Error: {}", e);
+            return;
+        }
+    };
 
-        let read_bytes = match file.read(&mut data_buffer) {
-            Ok(bytes) => bytes,
+    // In netascii, the size of the file is the size of the translated data
+    // sent to the client, which is only computed when the tsize option is
+    // requested. Without it, the local size is a lower bound for the
+    // rollover check, and the transfer stops if the block numbers run out
+    let file_size = match netascii && rrq.options.get("tsize").is_some() {
+        true => match netascii_size(&mut file).and_then(|size| file.rewind().map(|_| size)) {
+            Ok(size) => size,
             Err(e) => {
-                let err_packet =
-                    ERRORPacket::NotDefined(format!("An error occurs on the server: {}", e));
-                server_socket.send(&err_packet.as_bytes());
+                let err_packet = io_error_packet(&e, server_args.error_verbosity);
+                let _ = server_socket.send(&err_packet.as_bytes());
                 eprintln!("Error: {}", e);
                 return;
             }
-        };
+        },
+        false => file_size,
+    };
 
-        let data_packet =
-            DATAPacket::build(current_block_number, &data_buffer[..read_bytes]).unwrap();
+    let (transfer_options, oack) = negotiate_options(&rrq.options, server_args, Some(file_size));
 
-        if let Err(e) = server_socket.send(&data_packet.as_bytes()) {
-            eprintln!("Error: {}", e);
-            return;
-        }
+    // Refuse the download before any data is sent when
+    // the file cannot be numbered without rollover
+    if transfer_options.exceeds_block_numbers(file_size) {
+        let err_packet = too_large_error();
+        let _ = server_socket.send(&err_packet.as_bytes());
+        eprintln!("Error: {}", err_packet.get_error_message());
+        return;
+    }
 
-        // Creates buffer to store
-        // the ACK packet
-        let mut response = [0_u8; 4];
+    let mut rtt_estimator = RttEstimator::new(&transfer_options);
 
-        if let Err(e) = server_socket.recv(&mut response) {
+    // When options are acknowledged, the client
+    // confirms the OACK with an ACK packet (block 0)
+    // before the first TFTP DATA packet is sent
+    if !oack.options.is_empty() {
+        if let Err(e) = send_oack(&server_socket, &oack, &transfer_options, &mut rtt_estimator) {
             eprintln!("Error: {}", e);
             return;
         }
+    }
 
-        let ack = match TFTPPacket::parse(&response) {
-            Ok(TFTPPacket::ACK(packet)) => packet,
-            _ => {
-                let err_packet = ERRORPacket::IllegalTftpOperation;
-                server_socket.send(&err_packet.as_bytes());
-                eprintln!("Error: {}", err_packet.get_error_message());
-                return;
-            }
-        };
+    let mut reader: Box<dyn Read> = match netascii {
+        true => Box::new(NetasciiReader::new(file)),
+        false => Box::new(file),
+    };
 
-        if ack.block != current_block_number {
-            let err_packet = ERRORPacket::IllegalTftpOperation;
-            server_socket.send(&err_packet.as_bytes());
-            eprintln!("Error: {}", err_packet.get_error_message());
+    match send_file(
+        &server_socket,
+        &mut reader,
+        &transfer_options,
+        &mut rtt_estimator,
+    ) {
+        Ok(sent_bytes) => println!(
+            "File transmission is finished [DOWNLOAD] {}",
+            transfer_summary(sent_bytes, &rtt_estimator)
+        ),
+        Err(e) => eprintln!(
+            "Error: {}",
+            transfer_error(&server_socket, e, server_args.error_verbosity)
+        ),
+    }
+}
+
+fn client_write_to_server(
+    wrq: WRQPacket,
+    client_addr: SocketAddr,
+    local_addr: SocketAddr,
+    server_args: &ServerArgs,
+) {
+    let socket = match bind_session_socket(client_addr, local_addr) {
+        Ok(socket) => socket,
+        Err(e) => {
+            eprintln!("Error: Unable to initialize a session socket: {}", e);
             return;
         }
+    };
 
-        if read_bytes < 512 {
-            println!("File transmission is finished [DOWNLOAD]");
+    // Packets from any other source than
+    // the client are rejected (RFC 1350)
+    let server_socket = TransferSocket::new(socket, client_addr, warn_unknown_tid);
+
+    // Text files are translated in the
+    // netascii mode (RFC 764, RFC 1350)
+    let netascii = match wrq.mode {
+        TransferMode::Netascii => true,
+        TransferMode::Octet => false,
+        TransferMode::Mail => {
+            let err_packet = ERRORPacket::NotDefined(
+                "The server supports only the 'netascii' and 'octet' modes".to_string(),
+            );
+            let _ = server_socket.send(&err_packet.as_bytes());
+            eprintln!("Error: {}", err_packet.get_error_message());
             return;
         }
+    };
 
-        current_block_number += 1;
-    }
-}
-
-fn client_write_to_server(wrq: WRQPacket, client_addr: SocketAddr) {
-    let server_socket = loop {
-        let server_tid: u16 = rand::thread_rng().gen_range(0..65535);
+    let (transfer_options, oack) = negotiate_options(&wrq.options, server_args, None);
 
-        match UdpSocket::bind(format!("0.0.0.0:{}", server_tid)) {
-            Ok(socket) => break socket,
-            Err(_) => continue,
+    let file_path = match resolve_write_path(
+        &server_args.upload_dir,
+        &wrq.filename,
+        server_args.symlink_policy,
+    ) {
+        Ok(path) => path,
+        Err(e) => {
+            let _ =
+                server_socket.send(&path_error_packet(&e, server_args.error_verbosity).as_bytes());
+            eprintln!("Error: {} [{:?}]", e, wrq.filename);
+            return;
         }
     };
 
-    // Ensure connection with the client
-    if let Err(e) = server_socket.connect(client_addr) {
-        eprintln!("Error: {}", e);
-        return;
-    }
+    // Refuse the upload before any data is sent when
+    // the announced size (RFC 2349) cannot be stored
+    if let Some(transfer_size) = transfer_options.transfer_size {
+        if transfer_options.exceeds_block_numbers(transfer_size) {
+            let err_packet = too_large_error();
+            let _ = server_socket.send(&err_packet.as_bytes());
+            eprintln!("Error: {}", err_packet.get_error_message());
+            return;
+        }
 
-    if wrq.mode.to_lowercase() != "octet" {
-        let err_packet =
-            ERRORPacket::NotDefined("The server supports only the 'octet' mode".to_string());
-        server_socket.send(&err_packet.as_bytes());
-        eprintln!("Error: {}", err_packet.get_error_message());
-        return;
-    }
+        let exceeds_quota = server_args
+            .max_upload_size
+            .is_some_and(|max_upload_size| transfer_size > max_upload_size);
 
-    let ack = ACKPacket { block: 0 };
+        let exceeds_free_space = available_space(&server_args.upload_dir)
+            .is_ok_and(|free_space| transfer_size > free_space);
 
-    if let Err(e) = server_socket.send(&ack.as_bytes()) {
-        eprint!("Error: {}", e);
-        return;
+        if exceeds_quota || exceeds_free_space {
+            let err_packet = ERRORPacket::DiskFull;
+            let _ = server_socket.send(&err_packet.as_bytes());
+            eprintln!("Error: {}", err_packet.get_error_message());
+            return;
+        }
     }
 
-    let bin_dir = match env::current_exe() {
-        Ok(bin_path) => bin_path.parent().unwrap().to_owned(),
+    // The data is received into a temporary file, which
+    // is moved to the destination once the upload succeeds
+    let mut partial_upload = match PartialUpload::create(&file_path) {
+        Ok(partial_upload) => partial_upload,
         Err(e) => {
-            let err_packet =
-                ERRORPacket::NotDefined(format!("An error occurs on the server: {}", e));
-            server_socket.send(&err_packet.as_bytes());
+            let err_packet = io_error_packet(&e, server_args.error_verbosity);
+            let _ = server_socket.send(&err_packet.as_bytes());
             eprintln!("Error: {}", e);
             return;
         }
     };
 
-    let filename = match Path::new(&wrq.filename).file_name() {
-        Some(filename) => filename,
-        None => {
-            let err_packet = ERRORPacket::NotDefined("Invalid Filename".to_string());
-            server_socket.send(&err_packet.as_bytes());
-            eprintln!("Error: Invalid filename from client [UPLOAD]");
-            return;
-        }
+    // The OACK packet replaces the
+    // ACK packet (block 0) for write
+    let response = if oack.options.is_empty() {
+        ACKPacket { block: 0 }.as_bytes()
+    } else {
+        oack.as_bytes()
     };
 
-    let mut file = match OpenOptions::new()
-        .create_new(true)
-        .append(true)
-        .open(bin_dir.join(filename))
-    {
-        Ok(f) => f,
-        Err(e) => {
-            let err_packet =
-                ERRORPacket::NotDefined(format!("An error occurs on the server: {}", e));
-            server_socket.send(&err_packet.as_bytes());
-            eprintln!("Error: {}", e);
-            return;
+    if let Err(e) = server_socket.send(&response) {
+        eprintln!("Error: {}", e);
+        return;
+    }
+
+    let mut rtt_estimator = RttEstimator::new(&transfer_options);
+
+    let result = match netascii {
+        // The last CR of the data is only written
+        // once the whole file has been received
+        true => {
+            let mut writer = NetasciiWriter::new(partial_upload.file());
+
+            receive_file(
+                &server_socket,
+                &mut writer,
+                &transfer_options,
+                &mut rtt_estimator,
+                response,
+                None,
+            )
+            .map_err(|e| transfer_error(&server_socket, e, server_args.error_verbosity))
+            .and_then(|received_file| match writer.finish() {
+                Ok(_) => Ok(received_file),
+                Err(e) => Err(transfer_error(
+                    &server_socket,
+                    TransferError::File(e),
+                    server_args.error_verbosity,
+                )),
+            })
         }
+        false => receive_file(
+            &server_socket,
+            partial_upload.file(),
+            &transfer_options,
+            &mut rtt_estimator,
+            response,
+            None,
+        )
+        .map_err(|e| transfer_error(&server_socket, e, server_args.error_verbosity)),
     };
 
-    let mut last_block_number = 0;
+    // The final ACK packet is only sent once the file is stored,
+    // so that the client learns whether the upload succeeded
+    let result = result.and_then(|received_file| match partial_upload.complete() {
+        Ok(()) => {
+            acknowledge_file(
+                &server_socket,
+                &received_file,
+                &transfer_options,
+                &rtt_estimator,
+            );
+            Ok(received_file.bytes)
+        }
+        Err(e) => {
+            let err_packet = io_error_packet(&e, server_args.error_verbosity);
+            let _ = server_socket.send(&err_packet.as_bytes());
+            Err(format!("Unable to store the received file: {}", e))
+        }
+    });
+
+    match result {
+        Ok(received_bytes) => println!(
+            "File transmission is finished [UPLOAD] {}",
+            transfer_summary(received_bytes, &rtt_estimator)
+        ),
+        Err(e) => eprintln!("Error: {}", e),
+    }
+}
 
-    loop {
-        // Create a buffer to store
-        // a TFTP DATA packet (516 bytes)
-        let mut data_buffer = [0_u8; 516];
+/// Selects the options requested by a client that the server
+/// acknowledges in an OACK packet (RFC 2347).
+///
+/// Options not understood by the server, or with invalid values,
+/// are ignored, in which case the transfer falls back to RFC 1350.
+///
+/// `file_size` is the size of the requested file for a RRQ,
+/// and `None` for a WRQ where the client announces the size.
+fn negotiate_options(
+    requested_options: &TFTPOptions,
+    server_args: &ServerArgs,
+    file_size: Option<u64>,
+) -> (TransferOptions, OACKPacket) {
+    let mut transfer_options = TransferOptions {
+        timeout: server_args.timeout,
+        retries: server_args.retries,
+        rollover: server_args.rollover,
+        max_receive_size: server_args.max_upload_size,
+        // Packets of the session are parsed as strictly as requests
+        strictness: server_args.strictness,
+        ..Default::default()
+    };
+    let mut oack = OACKPacket {
+        options: TFTPOptions::new(),
+    };
 
-        let read_bytes = match server_socket.recv(&mut data_buffer) {
-            Ok(bytes) => bytes,
-            Err(e) => {
-                eprintln!("Error: {}", e);
-                return;
+    for (name, value) in requested_options.iter() {
+        match name {
+            // The server answers with a block size
+            // lower than or equal to the requested one (RFC 2348)
+            "blksize" => {
+                if let Ok(block_size) = value.parse::<usize>() {
+                    if block_size >= DATAPacket::MIN_BLOCK_SIZE {
+                        transfer_options.block_size = block_size.min(server_args.max_block_size);
+                        oack.options
+                            .insert(name, &transfer_options.block_size.to_string());
+                    }
+                }
             }
-        };
-
-        let data_packet = match TFTPPacket::parse(&data_buffer[..read_bytes]) {
-            Ok(TFTPPacket::DATA(packet)) => packet,
-            Ok(TFTPPacket::ERROR(err_packet)) => {
-                eprintln!("Error: {}", err_packet.get_error_message());
-                return;
+            // The server reports the size of the file for read and
+            // echoes the size announced by the client for write (RFC 2349)
+            "tsize" => {
+                if let Ok(transfer_size) = value.parse::<u64>() {
+                    match file_size {
+                        Some(file_size) => {
+                            oack.options.insert(name, &file_size.to_string());
+                        }
+                        None => {
+                            transfer_options.transfer_size = Some(transfer_size);
+                            oack.options.insert(name, value);
+                        }
+                    }
+                }
             }
-            _ => {
-                let err_packet = ERRORPacket::IllegalTftpOperation;
-                server_socket.send(&err_packet.as_bytes());
-                eprintln!("Error: {}", err_packet.get_error_message());
-                return;
+            // The timeout is a number of seconds
+            // between 1 and 255 (RFC 2349)
+            "timeout" => {
+                if let Ok(timeout @ 1..=255) = value.parse::<u64>() {
+                    transfer_options.timeout = Duration::from_secs(timeout);
+                    transfer_options.adaptive_timeout = false;
+                    oack.options.insert(name, value);
+                }
             }
-        };
-
-        if data_packet.block != (last_block_number + 1) {
-            let err_packet = ERRORPacket::IllegalTftpOperation;
-            server_socket.send(&err_packet.as_bytes());
-            eprintln!("Error: {}", err_packet.get_error_message());
-            return;
+            // The server answers with a window size
+            // lower than or equal to the requested one (RFC 7440)
+            "windowsize" => {
+                if let Ok(window_size @ 1..=65535) = value.parse::<usize>() {
+                    transfer_options.window_size = window_size.min(server_args.max_window_size);
+                    oack.options
+                        .insert(name, &transfer_options.window_size.to_string());
+                }
+            }
+            _ => {}
         }
+    }
 
-        let write_bytes = match file.write(data_packet.get_data()) {
-            Ok(bytes) => bytes,
-            Err(e) => {
-                let err_packet =
-                    ERRORPacket::NotDefined(format!("An error occurs on the server: {}", e));
-                server_socket.send(&err_packet.as_bytes());
-                eprintln!("Error: {}", e);
-                return;
-            }
-        };
+    (transfer_options, oack)
+}
 
-        let ack = ACKPacket {
-            block: data_packet.block,
-        };
+/// ERROR packet answering a filename that cannot be resolved.
+fn path_error_packet(path_error: &PathError, error_verbosity: ErrorVerbosity) -> ERRORPacket {
+    match path_error {
+        PathError::Violation(_) => ERRORPacket::AccessViolation,
+        PathError::Io(e) => io_error_packet(e, error_verbosity),
+    }
+}
 
-        if let Err(e) = server_socket.send(&ack.as_bytes()) {
-            eprintln!("Error: {}", e);
-            return;
-        }
+/// Describes the failure of a transfer, sending the client
+/// the ERROR packet matching a file error, if any.
+fn transfer_error(
+    server_socket: &TransferSocket,
+    transfer_error: TransferError,
+    error_verbosity: ErrorVerbosity,
+) -> String {
+    if let TransferError::File(e) = &transfer_error {
+        let _ = server_socket.send(&io_error_packet(e, error_verbosity).as_bytes());
+    }
 
-        if write_bytes < 512 {
-            println!("File transmission is finished [UPLOAD]");
-            return;
-        }
+    transfer_error.to_string()
+}
+
+/// Reports a packet rejected because it came
+/// from an unknown transfer ID.
+fn warn_unknown_tid(source: SocketAddr) {
+    eprintln!("Warning: packet from an unknown transfer ID ({})", source);
+}
 
-        last_block_number = data_packet.block;
+/// Describes the amount of data transferred during
+/// a session and its estimated round-trip time.
+fn transfer_summary(bytes: u64, rtt_estimator: &RttEstimator) -> String {
+    match rtt_estimator.smoothed_rtt() {
+        Some(rtt) => format!("({} bytes, estimated RTT: {:.2?})", bytes, rtt),
+        None => format!("({} bytes)", bytes),
     }
 }
+
+/// Returns the space available to the server on
+/// the filesystem containing `path`, in bytes.
+#[cfg(unix)]
+fn available_space(path: &Path) -> io::Result<u64> {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    let c_path = CString::new(path.as_os_str().as_bytes())?;
+
+    // SAFETY: `statvfs` only writes into the zeroed
+    // structure and `c_path` is a valid C string
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+/// Returns the space available to the server on
+/// the filesystem containing `path`, in bytes.
+#[cfg(not(unix))]
+fn available_space(_path: &Path) -> io::Result<u64> {
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
@@ -0,0 +1,105 @@
+use std::{
+    collections::{HashMap, HashSet},
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+/// Limits the number of concurrent sessions of the
+/// server, in total and for each client IP address.
+#[derive(Debug)]
+pub struct SessionLimits {
+    max_sessions: usize,
+    max_sessions_per_client: Option<usize>,
+
+    /// Ports (TIDs) of the sessions running
+    /// for each client IP address.
+    sessions: Mutex<HashMap<IpAddr, HashSet<u16>>>,
+}
+
+/// Reasons for which a session is not started.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionRefusal {
+    /// The maximum number of sessions is reached.
+    Busy,
+    /// A session is already running for the same
+    /// client TID, so the request is a retransmission.
+    Duplicate,
+}
+
+impl SessionLimits {
+    /// Constructs a new `SessionLimits`.
+    pub fn new(max_sessions: usize, max_sessions_per_client: Option<usize>) -> Arc<Self> {
+        Arc::new(Self {
+            max_sessions,
+            max_sessions_per_client,
+            sessions: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Reserves a session for the client at `client_addr`. The
+    /// session is released when the returned `SessionPermit` is dropped.
+    pub fn try_acquire(
+        self: &Arc<Self>,
+        client_addr: SocketAddr,
+    ) -> Result<SessionPermit, SessionRefusal> {
+        let mut sessions = self.lock_sessions();
+
+        let client_sessions = match sessions.get(&client_addr.ip()) {
+            Some(ports) if ports.contains(&client_addr.port()) => {
+                return Err(SessionRefusal::Duplicate)
+            }
+            Some(ports) => ports.len(),
+            None => 0,
+        };
+
+        let total_sessions: usize = sessions.values().map(HashSet::len).sum();
+
+        if total_sessions >= self.max_sessions
+            || self
+                .max_sessions_per_client
+                .is_some_and(|max_sessions| client_sessions >= max_sessions)
+        {
+            return Err(SessionRefusal::Busy);
+        }
+
+        sessions
+            .entry(client_addr.ip())
+            .or_default()
+            .insert(client_addr.port());
+
+        Ok(SessionPermit {
+            limits: Arc::clone(self),
+            client_addr,
+        })
+    }
+
+    fn lock_sessions(&self) -> MutexGuard<'_, HashMap<IpAddr, HashSet<u16>>> {
+        // The counts are only updated once the lock
+        // is held, so a poisoned lock is still valid
+        match self.sessions.lock() {
+            Ok(sessions) => sessions,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+/// A session reserved with `SessionLimits::try_acquire`.
+#[derive(Debug)]
+pub struct SessionPermit {
+    limits: Arc<SessionLimits>,
+    client_addr: SocketAddr,
+}
+
+impl Drop for SessionPermit {
+    fn drop(&mut self) {
+        let mut sessions = self.limits.lock_sessions();
+
+        if let Some(client_sessions) = sessions.get_mut(&self.client_addr.ip()) {
+            client_sessions.remove(&self.client_addr.port());
+
+            if client_sessions.is_empty() {
+                sessions.remove(&self.client_addr.ip());
+            }
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::{mpsc, Arc, Mutex},
+    thread::{self, JoinHandle},
+};
+
+pub use limits::{SessionLimits, SessionRefusal};
+
+mod limits;
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// A fixed number of threads running the sessions of the server,
+/// so that a transfer does not wait for the previous ones.
+pub struct WorkerPool {
+    sender: mpsc::Sender<Job>,
+    _workers: Vec<JoinHandle<()>>,
+}
+
+impl WorkerPool {
+    /// Constructs a new `WorkerPool` with `size` threads.
+    pub fn new(size: usize) -> Result<Self, String> {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+
+        let mut workers = Vec::with_capacity(size);
+
+        for id in 0..size {
+            let receiver = Arc::clone(&receiver);
+
+            let worker = thread::Builder::new()
+                .name(format!("session-{}", id))
+                .spawn(move || loop {
+                    // The lock is released as soon as a job is received
+                    let job = match receiver.lock() {
+                        Ok(receiver) => receiver.recv(),
+                        Err(_) => return,
+                    };
+
+                    match job {
+                        // A panicking session must not
+                        // take its worker down with it
+                        Ok(job) => {
+                            let _ = panic::catch_unwind(AssertUnwindSafe(job));
+                        }
+                        Err(_) => return,
+                    }
+                })
+                .map_err(|e| format!("Unable to start the session workers: {}", e))?;
+
+            workers.push(worker);
+        }
+
+        Ok(Self {
+            sender,
+            _workers: workers,
+        })
+    }
+
+    /// Runs `job` on the next available worker.
+    pub fn execute(&self, job: impl FnOnce() + Send + 'static) {
+        let _ = self.sender.send(Box::new(job));
+    }
+}
@@ -0,0 +1,413 @@
+use std::{
+    fmt,
+    fs::{self, File, OpenOptions},
+    io,
+    path::{Component, Path, PathBuf},
+};
+
+/// Longest filename accepted in a RRQ/WRQ packet, in bytes.
+pub const MAX_FILENAME_LENGTH: usize = 255;
+
+/// Determines whether the symbolic links found
+/// under a served directory may be followed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymlinkPolicy {
+    /// Symbolic links are followed wherever they lead.
+    Follow,
+    /// Symbolic links are followed as long as
+    /// they lead under the served directory.
+    FollowWithinRoot,
+    /// Paths containing a symbolic link are refused.
+    Deny,
+}
+
+/// Reasons for which a filename requested by a client is not resolved.
+#[derive(Debug)]
+pub enum PathError {
+    /// The filename is refused, which is an access violation.
+    Violation(&'static str),
+    /// The path could not be inspected.
+    Io(io::Error),
+}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Violation(reason) => write!(f, "{}", reason),
+            Self::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// Resolves the file requested by a RRQ under `root`,
+/// which must be a canonical path.
+pub fn resolve_read_path(
+    root: &Path,
+    filename: &str,
+    symlink_policy: SymlinkPolicy,
+) -> Result<PathBuf, PathError> {
+    let path = root.join(relative_path(filename)?);
+
+    check_symlinks(root, &path, symlink_policy)
+}
+
+/// Resolves the file to be created by a WRQ under `root`,
+/// which must be a canonical path.
+///
+/// Only the parent directory, which must exist, is checked:
+/// an existing file, or symbolic link, is not replaced.
+pub fn resolve_write_path(
+    root: &Path,
+    filename: &str,
+    symlink_policy: SymlinkPolicy,
+) -> Result<PathBuf, PathError> {
+    let path = root.join(relative_path(filename)?);
+
+    // The relative path has at least one component
+    let parent = check_symlinks(root, path.parent().unwrap(), symlink_policy)?;
+
+    Ok(parent.join(path.file_name().unwrap()))
+}
+
+/// Opens a file resolved by `resolve_read_path` for reading.
+///
+/// The file is opened without blocking, so that a FIFO does not hold
+/// the session. Unless symbolic links are followed, the file is not
+/// opened if it was replaced by a symbolic link since the path was
+/// resolved. `O_NOFOLLOW` only covers the last component: a directory
+/// of the path replaced by a symbolic link in the meantime is followed.
+pub fn open_read_path(path: &Path, symlink_policy: SymlinkPolicy) -> io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.read(true);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+
+        options.custom_flags(match symlink_policy {
+            SymlinkPolicy::Follow => libc::O_NONBLOCK,
+            _ => libc::O_NONBLOCK | libc::O_NOFOLLOW,
+        });
+    }
+
+    #[cfg(not(unix))]
+    let _ = symlink_policy;
+
+    options.open(path)
+}
+
+/// Converts a filename into a path relative to the served directory,
+/// refusing any filename that could lead outside of it.
+fn relative_path(filename: &str) -> Result<PathBuf, PathError> {
+    if filename.len() > MAX_FILENAME_LENGTH {
+        return Err(PathError::Violation("Filename too long"));
+    }
+
+    // Including the NUL character
+    if filename.chars().any(char::is_control) {
+        return Err(PathError::Violation("Control character in the filename"));
+    }
+
+    let mut relative_path = PathBuf::new();
+
+    for component in Path::new(filename).components() {
+        match component {
+            Component::Normal(name) => relative_path.push(name),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                return Err(PathError::Violation("Parent directory in the filename"))
+            }
+            Component::RootDir | Component::Prefix(_) => {
+                return Err(PathError::Violation("Absolute filename"))
+            }
+        }
+    }
+
+    if relative_path.as_os_str().is_empty() {
+        return Err(PathError::Violation("Empty filename"));
+    }
+
+    Ok(relative_path)
+}
+
+/// Applies `symlink_policy` to `path`, located under `root`,
+/// returning the path to open.
+///
+/// Symbolic links followed within `root` are resolved in the returned
+/// path. The path is only checked, not opened: a component replaced
+/// by a symbolic link before the file is opened is followed, except
+/// for the last one (see `open_read_path`).
+fn check_symlinks(
+    root: &Path,
+    path: &Path,
+    symlink_policy: SymlinkPolicy,
+) -> Result<PathBuf, PathError> {
+    match symlink_policy {
+        SymlinkPolicy::Follow => Ok(path.to_path_buf()),
+        SymlinkPolicy::FollowWithinRoot => {
+            let canonical_path = fs::canonicalize(path).map_err(PathError::Io)?;
+
+            if !canonical_path.starts_with(root) {
+                return Err(PathError::Violation(
+                    "Symbolic link leading outside of the served directory",
+                ));
+            }
+
+            Ok(canonical_path)
+        }
+        SymlinkPolicy::Deny => {
+            let mut current_path = root.to_path_buf();
+
+            // `path` is built by joining a relative path to `root`
+            for component in path.strip_prefix(root).unwrap().components() {
+                current_path.push(component);
+
+                let metadata = fs::symlink_metadata(&current_path).map_err(PathError::Io)?;
+
+                if metadata.file_type().is_symlink() {
+                    return Err(PathError::Violation("Symbolic link in the path"));
+                }
+            }
+
+            Ok(current_path)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        process,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            mpsc,
+        },
+        thread,
+        time::Duration,
+    };
+
+    use super::*;
+
+    static TEMP_DIR_COUNT: AtomicU64 = AtomicU64::new(0);
+
+    /// Temporary directory holding a served directory
+    /// and its surroundings, removed once dropped.
+    struct TempDir {
+        path: PathBuf,
+    }
+
+    impl TempDir {
+        fn new() -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "tftpserver-resolver-{}-{}",
+                process::id(),
+                TEMP_DIR_COUNT.fetch_add(1, Ordering::Relaxed)
+            ));
+
+            fs::create_dir_all(path.join("root/dir")).unwrap();
+            fs::write(path.join("root/dir/file"), b"served").unwrap();
+            fs::write(path.join("secret"), b"not served").unwrap();
+
+            Self {
+                path: fs::canonicalize(path).unwrap(),
+            }
+        }
+
+        fn root(&self) -> PathBuf {
+            self.path.join("root")
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.path);
+        }
+    }
+
+    const POLICIES: [SymlinkPolicy; 3] = [
+        SymlinkPolicy::Follow,
+        SymlinkPolicy::FollowWithinRoot,
+        SymlinkPolicy::Deny,
+    ];
+
+    fn is_violation(result: Result<PathBuf, PathError>) -> bool {
+        matches!(result, Err(PathError::Violation(_)))
+    }
+
+    #[test]
+    fn relative_path_keeps_normal_components() {
+        assert_eq!(relative_path("dir/file").unwrap(), Path::new("dir/file"));
+        assert_eq!(
+            relative_path("./dir/./file").unwrap(),
+            Path::new("dir/file")
+        );
+    }
+
+    #[test]
+    fn relative_path_refuses_parent_and_absolute_paths() {
+        for filename in ["..", "../secret", "dir/../../secret", "/etc/passwd", "/"] {
+            assert!(
+                is_violation(relative_path(filename)),
+                "{:?} was accepted",
+                filename
+            );
+        }
+    }
+
+    #[test]
+    fn relative_path_refuses_control_characters() {
+        for filename in ["file\0", "dir\0/file", "file\n", "\x1bfile", "file\x7f"] {
+            assert!(
+                is_violation(relative_path(filename)),
+                "{:?} was accepted",
+                filename
+            );
+        }
+    }
+
+    #[test]
+    fn relative_path_limits_the_filename_length() {
+        assert!(relative_path(&"a".repeat(MAX_FILENAME_LENGTH)).is_ok());
+        assert!(is_violation(relative_path(
+            &"a".repeat(MAX_FILENAME_LENGTH + 1)
+        )));
+    }
+
+    #[test]
+    fn relative_path_refuses_empty_filenames() {
+        for filename in ["", ".", "./", "./."] {
+            assert!(
+                is_violation(relative_path(filename)),
+                "{:?} was accepted",
+                filename
+            );
+        }
+    }
+
+    #[test]
+    fn resolve_read_path_serves_files_under_the_root() {
+        let temp_dir = TempDir::new();
+        let root = temp_dir.root();
+
+        for symlink_policy in POLICIES {
+            let path = resolve_read_path(&root, "dir/file", symlink_policy).unwrap();
+
+            assert_eq!(path, root.join("dir/file"));
+            assert_eq!(fs::read(path).unwrap(), b"served");
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_leaving_the_root_follows_the_policy() {
+        let temp_dir = TempDir::new();
+        let root = temp_dir.root();
+
+        std::os::unix::fs::symlink(temp_dir.path.join("secret"), root.join("link")).unwrap();
+
+        let path = resolve_read_path(&root, "link", SymlinkPolicy::Follow).unwrap();
+        assert_eq!(fs::read(path).unwrap(), b"not served");
+
+        assert!(is_violation(resolve_read_path(
+            &root,
+            "link",
+            SymlinkPolicy::FollowWithinRoot
+        )));
+        assert!(is_violation(resolve_read_path(
+            &root,
+            "link",
+            SymlinkPolicy::Deny
+        )));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_within_the_root_follows_the_policy() {
+        let temp_dir = TempDir::new();
+        let root = temp_dir.root();
+
+        std::os::unix::fs::symlink(root.join("dir"), root.join("link")).unwrap();
+
+        for symlink_policy in [SymlinkPolicy::Follow, SymlinkPolicy::FollowWithinRoot] {
+            let path = resolve_read_path(&root, "link/file", symlink_policy).unwrap();
+            assert_eq!(fs::read(path).unwrap(), b"served");
+        }
+
+        assert!(is_violation(resolve_read_path(
+            &root,
+            "link/file",
+            SymlinkPolicy::Deny
+        )));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn resolve_write_path_checks_the_parent_directory() {
+        let temp_dir = TempDir::new();
+        let root = temp_dir.root();
+
+        std::os::unix::fs::symlink(&temp_dir.path, root.join("out")).unwrap();
+
+        assert_eq!(
+            resolve_write_path(&root, "dir/new", SymlinkPolicy::Deny).unwrap(),
+            root.join("dir/new")
+        );
+        assert!(resolve_write_path(&root, "out/new", SymlinkPolicy::Follow).is_ok());
+        assert!(is_violation(resolve_write_path(
+            &root,
+            "out/new",
+            SymlinkPolicy::FollowWithinRoot
+        )));
+        assert!(is_violation(resolve_write_path(
+            &root,
+            "out/new",
+            SymlinkPolicy::Deny
+        )));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn open_read_path_does_not_follow_a_swapped_symlink() {
+        let temp_dir = TempDir::new();
+        let root = temp_dir.root();
+
+        let path = resolve_read_path(&root, "dir/file", SymlinkPolicy::Deny).unwrap();
+
+        fs::remove_file(&path).unwrap();
+        std::os::unix::fs::symlink(temp_dir.path.join("secret"), &path).unwrap();
+
+        assert!(open_read_path(&path, SymlinkPolicy::Deny).is_err());
+        assert!(open_read_path(&path, SymlinkPolicy::FollowWithinRoot).is_err());
+        assert!(open_read_path(&path, SymlinkPolicy::Follow).is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn open_read_path_does_not_block_on_a_fifo() {
+        let temp_dir = TempDir::new();
+        let root = temp_dir.root();
+
+        let fifo_path =
+            std::ffi::CString::new(root.join("fifo").into_os_string().into_encoded_bytes())
+                .unwrap();
+        assert_eq!(unsafe { libc::mkfifo(fifo_path.as_ptr(), 0o600) }, 0);
+
+        let path = resolve_read_path(&root, "fifo", SymlinkPolicy::Deny).unwrap();
+
+        // Opening the FIFO would block until a writer opens
+        // it, so it is opened from another thread
+        let (sender, receiver) = mpsc::channel();
+
+        thread::spawn(move || {
+            let _ = sender.send(open_read_path(&path, SymlinkPolicy::Deny));
+        });
+
+        let file = receiver
+            .recv_timeout(Duration::from_secs(5))
+            .expect("opening the FIFO blocked")
+            .unwrap();
+
+        // The server refuses anything but a regular file
+        assert!(!file.metadata().unwrap().is_file());
+    }
+}
@@ -0,0 +1,93 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io,
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+/// Number of temporary files created so far,
+/// which tells apart concurrent uploads.
+static UPLOAD_COUNT: AtomicU64 = AtomicU64::new(0);
+
+/// Temporary file receiving an upload in the directory of its
+/// destination, so that the destination only appears once the
+/// whole file has been received.
+///
+/// The temporary file is removed when the upload is dropped before
+/// completion, so that a failed upload leaves no partial file.
+#[derive(Debug)]
+pub struct PartialUpload {
+    file: File,
+    temp_path: PathBuf,
+    destination: PathBuf,
+    completed: bool,
+}
+
+impl PartialUpload {
+    /// Creates the temporary file of an upload into `destination`,
+    /// which is refused up front if it already exists.
+    pub fn create(destination: &Path) -> io::Result<Self> {
+        // An existing symbolic link is not followed either
+        if destination.symlink_metadata().is_ok() {
+            return Err(io::Error::from(io::ErrorKind::AlreadyExists));
+        }
+
+        let file_name = destination
+            .file_name()
+            .ok_or(io::Error::from(io::ErrorKind::InvalidInput))?;
+
+        let temp_path = destination.with_file_name(format!(
+            ".{}.{}-{}.part",
+            file_name.to_string_lossy(),
+            process::id(),
+            UPLOAD_COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temp_path)?;
+
+        Ok(Self {
+            file,
+            temp_path,
+            destination: destination.to_owned(),
+            completed: false,
+        })
+    }
+
+    /// Returns the temporary file receiving the data.
+    pub fn file(&mut self) -> &mut File {
+        &mut self.file
+    }
+
+    /// Moves the received file to its destination, unless
+    /// another upload created it in the meantime.
+    pub fn complete(mut self) -> io::Result<()> {
+        self.file.sync_all()?;
+
+        // A hard link is never created over an existing file,
+        // unlike a rename, which replaces it
+        match fs::hard_link(&self.temp_path, &self.destination) {
+            Ok(()) => fs::remove_file(&self.temp_path)?,
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => return Err(e),
+            // Filesystems without hard links
+            Err(_) if self.destination.symlink_metadata().is_ok() => {
+                return Err(io::Error::from(io::ErrorKind::AlreadyExists))
+            }
+            Err(_) => fs::rename(&self.temp_path, &self.destination)?,
+        }
+
+        self.completed = true;
+        Ok(())
+    }
+}
+
+impl Drop for PartialUpload {
+    fn drop(&mut self) {
+        if !self.completed {
+            let _ = fs::remove_file(&self.temp_path);
+        }
+    }
+}
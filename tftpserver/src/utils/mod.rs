@@ -0,0 +1,467 @@
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    net::{IpAddr, Ipv6Addr, SocketAddr, SocketAddrV6},
+    path::{Path, PathBuf},
+    process,
+    time::Duration,
+};
+
+use tftppacket::{BlockRollover, DATAPacket, Strictness};
+use tftptransfer::TransferOptions;
+
+use crate::{errors::ErrorVerbosity, resolver::SymlinkPolicy};
+
+/// Contains all arguments accepted by the TFTP server.
+///
+/// Options may also be read from a configuration file
+/// (`--config <FILE>`), which the command line overrides.
+#[derive(Debug)]
+pub struct ServerArgs {
+    /// Largest block size granted to a client (RFC 2348).
+    pub max_block_size: usize,
+
+    /// Largest window size granted to a client (RFC 7440).
+    pub max_window_size: usize,
+
+    /// Largest file accepted for upload, in bytes.
+    pub max_upload_size: Option<u64>,
+
+    /// Timeout of a transfer, unless negotiated.
+    pub timeout: Duration,
+
+    /// Retransmissions of a packet before a transfer is aborted.
+    pub retries: u32,
+
+    /// Block number following 65535.
+    pub rollover: BlockRollover,
+
+    /// Largest number of concurrent sessions.
+    pub max_sessions: usize,
+
+    /// Largest number of concurrent sessions of a client IP address.
+    pub max_sessions_per_client: Option<usize>,
+
+    /// Directory serving downloads.
+    pub read_dir: PathBuf,
+
+    /// Directory storing uploads.
+    pub upload_dir: PathBuf,
+
+    /// Policy applied to symbolic links under the served directories.
+    pub symlink_policy: SymlinkPolicy,
+
+    /// Detail given to clients about server failures.
+    pub error_verbosity: ErrorVerbosity,
+
+    /// Deviations from the RFCs tolerated in client packets.
+    pub strictness: Strictness,
+
+    /// Addresses on which requests are received.
+    pub listen_addrs: Vec<SocketAddr>,
+}
+
+impl ServerArgs {
+    pub const DEFAULT_MAX_SESSIONS: usize = 64;
+    pub const DEFAULT_PORT: u16 = 69;
+
+    /// Constructs a new instance of `ServerArgs`.
+    pub fn build() -> Result<Self, String> {
+        let cli_args = env::args().skip(1).collect::<Vec<String>>();
+
+        // The options of the configuration file come
+        // first, so that the command line overrides them
+        let mut args = match config_path(&cli_args)? {
+            Some(config_path) => read_config(config_path)?,
+            None => Vec::new(),
+        };
+
+        args.extend(cli_args);
+
+        let mut max_block_size = DATAPacket::MAX_BLOCK_SIZE;
+        let mut max_window_size = 65535;
+        let mut max_upload_size = None;
+        let mut timeout = TransferOptions::DEFAULT_TIMEOUT;
+        let mut retries = TransferOptions::DEFAULT_RETRIES;
+        let mut rollover = BlockRollover::ToZero;
+        let mut max_sessions = Self::DEFAULT_MAX_SESSIONS;
+        let mut max_sessions_per_client = None;
+        let mut root_dir = None;
+        let mut read_root = None;
+        let mut upload_dir = None;
+        let mut symlink_policy = SymlinkPolicy::FollowWithinRoot;
+        let mut error_verbosity = ErrorVerbosity::Minimal;
+        let mut strictness = Strictness::Lenient;
+        let mut listen_values = Vec::new();
+        let mut port = Self::DEFAULT_PORT;
+
+        let mut options = args.iter();
+
+        while let Some(option) = options.next() {
+            match option.as_str() {
+                "--max-blksize" => {
+                    let value = options
+                        .next()
+                        .ok_or(String::from("Missing value for [--max-blksize]"))?;
+
+                    match value.parse::<usize>() {
+                        Ok(size)
+                            if (DATAPacket::MIN_BLOCK_SIZE..=DATAPacket::MAX_BLOCK_SIZE)
+                                .contains(&size) =>
+                        {
+                            max_block_size = size
+                        }
+                        _ => return Err(String::from("Invalid [--max-blksize] (8..65464)")),
+                    }
+                }
+                "--max-windowsize" => {
+                    let value = options
+                        .next()
+                        .ok_or(String::from("Missing value for [--max-windowsize]"))?;
+
+                    match value.parse::<usize>() {
+                        Ok(size @ 1..=65535) => max_window_size = size,
+                        _ => return Err(String::from("Invalid [--max-windowsize] (1..65535)")),
+                    }
+                }
+                "--max-upload-size" => {
+                    let value = options
+                        .next()
+                        .ok_or(String::from("Missing value for [--max-upload-size]"))?;
+
+                    match value.parse::<u64>() {
+                        Ok(size) => max_upload_size = Some(size),
+                        Err(_) => return Err(String::from("Invalid [--max-upload-size]")),
+                    }
+                }
+                "--timeout" => {
+                    let value = options
+                        .next()
+                        .ok_or(String::from("Missing value for [--timeout]"))?;
+
+                    match value.parse::<u64>() {
+                        Ok(seconds @ 1..=255) => timeout = Duration::from_secs(seconds),
+                        _ => return Err(String::from("Invalid [--timeout] (1..255)")),
+                    }
+                }
+                "--retries" => {
+                    let value = options
+                        .next()
+                        .ok_or(String::from("Missing value for [--retries]"))?;
+
+                    match value.parse::<u32>() {
+                        Ok(count) => retries = count,
+                        Err(_) => return Err(String::from("Invalid [--retries]")),
+                    }
+                }
+                "--rollover" => {
+                    let value = options
+                        .next()
+                        .ok_or(String::from("Missing value for [--rollover]"))?;
+
+                    rollover = match value.as_str() {
+                        "0" => BlockRollover::ToZero,
+                        "1" => BlockRollover::ToOne,
+                        "none" => BlockRollover::Disabled,
+                        _ => return Err(String::from("Invalid [--rollover] (0, 1 or none)")),
+                    };
+                }
+                "--max-sessions" => {
+                    let value = options
+                        .next()
+                        .ok_or(String::from("Missing value for [--max-sessions]"))?;
+
+                    match value.parse::<usize>() {
+                        Ok(count) if count >= 1 => max_sessions = count,
+                        _ => return Err(String::from("Invalid [--max-sessions]")),
+                    }
+                }
+                "--max-sessions-per-client" => {
+                    let value = options.next().ok_or(String::from(
+                        "Missing value for [--max-sessions-per-client]",
+                    ))?;
+
+                    match value.parse::<usize>() {
+                        Ok(count) if count >= 1 => max_sessions_per_client = Some(count),
+                        _ => return Err(String::from("Invalid [--max-sessions-per-client]")),
+                    }
+                }
+                "--root" => {
+                    let value = options
+                        .next()
+                        .ok_or(String::from("Missing value for [--root]"))?;
+
+                    root_dir = Some(PathBuf::from(value));
+                }
+                "--read-root" => {
+                    let value = options
+                        .next()
+                        .ok_or(String::from("Missing value for [--read-root]"))?;
+
+                    read_root = Some(PathBuf::from(value));
+                }
+                "--upload-dir" => {
+                    let value = options
+                        .next()
+                        .ok_or(String::from("Missing value for [--upload-dir]"))?;
+
+                    upload_dir = Some(PathBuf::from(value));
+                }
+                "--symlinks" => {
+                    let value = options
+                        .next()
+                        .ok_or(String::from("Missing value for [--symlinks]"))?;
+
+                    symlink_policy = match value.as_str() {
+                        "follow" => SymlinkPolicy::Follow,
+                        "within-root" => SymlinkPolicy::FollowWithinRoot,
+                        "deny" => SymlinkPolicy::Deny,
+                        _ => {
+                            return Err(String::from(
+                                "Invalid [--symlinks] (follow, within-root or deny)",
+                            ))
+                        }
+                    };
+                }
+                "--error-messages" => {
+                    let value = options
+                        .next()
+                        .ok_or(String::from("Missing value for [--error-messages]"))?;
+
+                    error_verbosity = match value.as_str() {
+                        "minimal" => ErrorVerbosity::Minimal,
+                        "detailed" => ErrorVerbosity::Detailed,
+                        _ => {
+                            return Err(String::from(
+                                "Invalid [--error-messages] (minimal or detailed)",
+                            ))
+                        }
+                    };
+                }
+                "--parsing" => {
+                    let value = options
+                        .next()
+                        .ok_or(String::from("Missing value for [--parsing]"))?;
+
+                    strictness = match value.as_str() {
+                        "lenient" => Strictness::Lenient,
+                        "strict" => Strictness::Strict,
+                        _ => return Err(String::from("Invalid [--parsing] (lenient or strict)")),
+                    };
+                }
+                "--listen" => {
+                    let value = options
+                        .next()
+                        .ok_or(String::from("Missing value for [--listen]"))?;
+
+                    listen_values.push(value);
+                }
+                "--port" => {
+                    let value = options
+                        .next()
+                        .ok_or(String::from("Missing value for [--port]"))?;
+
+                    match value.parse::<u16>() {
+                        Ok(number) => port = number,
+                        Err(_) => return Err(String::from("Invalid [--port] (0..65535)")),
+                    }
+                }
+                // Already read by `config_path`
+                "--config" => {
+                    options.next();
+                }
+                _ => return Err(format!("Invalid option: {}", option)),
+            }
+        }
+
+        // Addresses without a port use `--port`,
+        // whatever the order of both options
+        let listen_addrs = match listen_values.is_empty() {
+            true => vec![SocketAddr::from(([0, 0, 0, 0], port))],
+            false => listen_values
+                .into_iter()
+                .map(|value| parse_listen_addr(value, port))
+                .collect::<Result<Vec<_>, String>>()?,
+        };
+
+        // Without a root, files are served from
+        // the directory of the executable
+        let root_dir = match root_dir {
+            Some(root_dir) => root_dir,
+            None => env::current_exe()
+                .map_err(|e| format!("Unable to locate the server executable: {}", e))?
+                .parent()
+                .unwrap()
+                .to_owned(),
+        };
+
+        // Problems are reported before the server starts
+        let read_dir = validate_dir(read_root.as_ref().unwrap_or(&root_dir), false)?;
+        let upload_dir = validate_dir(upload_dir.as_ref().unwrap_or(&root_dir), true)?;
+
+        Ok(Self {
+            max_block_size,
+            max_window_size,
+            max_upload_size,
+            timeout,
+            retries,
+            rollover,
+            max_sessions,
+            max_sessions_per_client,
+            read_dir,
+            upload_dir,
+            symlink_policy,
+            error_verbosity,
+            strictness,
+            listen_addrs,
+        })
+    }
+}
+
+/// Returns the path given to the `--config` option, if any.
+fn config_path(args: &[String]) -> Result<Option<&Path>, String> {
+    match args.iter().position(|arg| arg == "--config") {
+        Some(index) => match args.get(index + 1) {
+            Some(path) => Ok(Some(Path::new(path))),
+            None => Err(String::from("Missing value for [--config]")),
+        },
+        None => Ok(None),
+    }
+}
+
+/// Reads the options of a configuration file as command-line arguments.
+///
+/// Each line holds an option without its leading dashes and its value,
+/// such as `root = /srv/tftp`. Empty lines and lines starting with `#`
+/// are ignored.
+fn read_config(path: &Path) -> Result<Vec<String>, String> {
+    let content = fs::read_to_string(path).map_err(|e| {
+        format!(
+            "Unable to read the configuration file {}: {}",
+            path.display(),
+            e
+        )
+    })?;
+
+    let mut args = Vec::new();
+
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        match line.split_once('=') {
+            Some((name, value)) if !name.trim().is_empty() && name.trim() != "config" => {
+                args.push(format!("--{}", name.trim()));
+                args.push(value.trim().to_string());
+            }
+            _ => {
+                return Err(format!(
+                    "Invalid line {} in the configuration file {}",
+                    index + 1,
+                    path.display()
+                ))
+            }
+        }
+    }
+
+    Ok(args)
+}
+
+/// Parses a listen address, such as `192.0.2.1`, `0.0.0.0:1069`,
+/// `::`, `[::]:1069`, `fe80::1%eth0` or `[fe80::1%2]:1069`.
+///
+/// Addresses without a port use `default_port`.
+fn parse_listen_addr(value: &str, default_port: u16) -> Result<SocketAddr, String> {
+    let invalid_addr = || format!("Invalid [--listen] address: {}", value);
+
+    if let Ok(addr) = value.parse::<SocketAddr>() {
+        return Ok(addr);
+    }
+
+    if let Ok(ip) = value.parse::<IpAddr>() {
+        return Ok(SocketAddr::new(ip, default_port));
+    }
+
+    // An IPv6 address with a scope ID, which
+    // may be enclosed in brackets with a port
+    let (host, port) = match value.strip_prefix('[') {
+        Some(rest) => match rest.split_once(']') {
+            Some((host, "")) => (host, default_port),
+            Some((host, port)) => match port.strip_prefix(':').map(str::parse::<u16>) {
+                Some(Ok(port)) => (host, port),
+                _ => return Err(invalid_addr()),
+            },
+            None => return Err(invalid_addr()),
+        },
+        None => (value, default_port),
+    };
+
+    let (ip, scope_id) = match host.split_once('%') {
+        Some((ip, scope)) => (ip, parse_scope_id(scope).ok_or_else(invalid_addr)?),
+        None => (host, 0),
+    };
+
+    match ip.parse::<Ipv6Addr>() {
+        Ok(ip) => Ok(SocketAddr::V6(SocketAddrV6::new(ip, port, 0, scope_id))),
+        Err(_) => Err(invalid_addr()),
+    }
+}
+
+/// Returns the index of an interface, given
+/// either as a number or by its name.
+fn parse_scope_id(scope: &str) -> Option<u32> {
+    if let Ok(index) = scope.parse::<u32>() {
+        return Some(index);
+    }
+
+    interface_index(scope)
+}
+
+#[cfg(unix)]
+fn interface_index(name: &str) -> Option<u32> {
+    let name = std::ffi::CString::new(name).ok()?;
+
+    // SAFETY: `name` is a valid NUL-terminated string
+    match unsafe { libc::if_nametoindex(name.as_ptr()) } {
+        0 => None,
+        index => Some(index),
+    }
+}
+
+#[cfg(not(unix))]
+fn interface_index(_name: &str) -> Option<u32> {
+    None
+}
+
+/// Ensures that `path` is a readable directory, which is also
+/// writable when it stores uploads, and returns its canonical path.
+fn validate_dir(path: &Path, writable: bool) -> Result<PathBuf, String> {
+    let invalid_dir = |e: String| format!("Invalid directory {}: {}", path.display(), e);
+
+    let dir = fs::canonicalize(path).map_err(|e| invalid_dir(e.to_string()))?;
+
+    if !dir.is_dir() {
+        return Err(invalid_dir(String::from("Not a directory")));
+    }
+
+    fs::read_dir(&dir).map_err(|e| invalid_dir(e.to_string()))?;
+
+    // The permissions of the directory do not tell whether
+    // the server may write into it, so a file is created
+    if writable {
+        let probe_path = dir.join(format!(".tftpserver-{}", process::id()));
+
+        OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&probe_path)
+            .map_err(|e| invalid_dir(e.to_string()))?;
+
+        let _ = fs::remove_file(probe_path);
+    }
+
+    Ok(dir)
+}
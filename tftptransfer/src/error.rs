@@ -0,0 +1,28 @@
+use std::{error, fmt, io};
+
+/// Represents the reasons why a transfer did not complete.
+#[derive(Debug)]
+pub enum TransferError {
+    /// The transfer was aborted with an ERROR packet sent to the peer.
+    Aborted(String),
+    /// The peer aborted the transfer with an ERROR
+    /// packet, whose code and message are kept.
+    Peer { code: u16, message: String },
+    /// The socket of the transfer failed.
+    Socket(io::Error),
+    /// The file could not be read or written. No ERROR packet
+    /// is sent, so that the caller chooses the one to send.
+    File(io::Error),
+}
+
+impl fmt::Display for TransferError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Aborted(message) => write!(f, "{}", message),
+            Self::Peer { message, .. } => write!(f, "{}", message),
+            Self::Socket(e) | Self::File(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl error::Error for TransferError {}
@@ -0,0 +1,1154 @@
+use std::{
+    collections::VecDeque,
+    io::{self, Read, Write},
+    time::{Duration, Instant},
+};
+
+use tftppacket::{
+    ACKPacket, BlockNumber, BlockRollover, DATAPacket, DATAPacketRef, ERRORPacket, ERRORPacketRef,
+    OACKPacket, PacketError, PacketRef, Strictness,
+};
+
+pub use error::TransferError;
+pub use rtt::RttEstimator;
+pub use socket::{reject_unknown_tid, TransferSocket};
+
+mod error;
+mod rtt;
+mod socket;
+
+/// Contains the transfer parameters negotiated with the peer,
+/// along with the retransmission limits of this host.
+#[derive(Debug)]
+pub struct TransferOptions {
+    /// Number of data bytes in a full TFTP DATA packet (RFC 2348).
+    pub block_size: usize,
+
+    /// Size of the file announced by the peer (RFC 2349).
+    pub transfer_size: Option<u64>,
+
+    /// Interval to wait for a packet from the peer
+    /// before retransmitting the last packet (RFC 2349).
+    pub timeout: Duration,
+
+    /// Whether the timeout adapts to the round-trip time of the
+    /// session, unless it was negotiated (RFC 2349).
+    pub adaptive_timeout: bool,
+
+    /// Number of retransmissions of the same
+    /// packet before the transfer is aborted.
+    pub retries: u32,
+
+    /// Number of TFTP DATA packets sent before
+    /// waiting for an ACK packet (RFC 7440).
+    pub window_size: usize,
+
+    /// Block number following 65535, for
+    /// transfers of more than 65535 blocks.
+    pub rollover: BlockRollover,
+
+    /// Number of bytes beyond which a received file
+    /// is refused with a `DiskFull` ERROR packet.
+    pub max_receive_size: Option<u64>,
+
+    /// Deviations from the RFCs tolerated in peer packets.
+    pub strictness: Strictness,
+}
+
+impl TransferOptions {
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+    pub const DEFAULT_RETRIES: u32 = 5;
+
+    /// Whether a transfer of `size` bytes needs more
+    /// block numbers than available without rollover.
+    pub fn exceeds_block_numbers(&self, size: u64) -> bool {
+        self.rollover == BlockRollover::Disabled
+            && size / self.block_size as u64 >= BlockNumber::MAX_BLOCKS
+    }
+}
+
+impl Default for TransferOptions {
+    fn default() -> Self {
+        Self {
+            block_size: DATAPacket::DEFAULT_BLOCK_SIZE,
+            transfer_size: None,
+            timeout: Self::DEFAULT_TIMEOUT,
+            adaptive_timeout: true,
+            retries: Self::DEFAULT_RETRIES,
+            window_size: 1,
+            rollover: BlockRollover::ToZero,
+            max_receive_size: None,
+            strictness: Strictness::Lenient,
+        }
+    }
+}
+
+/// Sends the OACK packet answering a RRQ and waits for the
+/// ACK packet (block 0) confirming it (RFC 2347).
+///
+/// The OACK packet is sent again each time the timeout expires.
+pub fn send_oack(
+    socket: &TransferSocket,
+    oack: &OACKPacket,
+    transfer_options: &TransferOptions,
+    rtt_estimator: &mut RttEstimator,
+) -> Result<(), TransferError> {
+    let mut timeouts = 0;
+
+    // Creates buffer to store the ACK packet, large
+    // enough for an ERROR packet with a long message
+    let mut response = vec![0_u8; transfer_options.block_size + 5];
+
+    loop {
+        socket
+            .send(&oack.as_bytes())
+            .map_err(TransferError::Socket)?;
+
+        let sent_at = Instant::now();
+
+        let recv_bytes = match recv_packet(socket, &mut response, rtt_estimator.rto())? {
+            Some(bytes) => bytes,
+            None => {
+                timeouts += 1;
+
+                if timeouts > transfer_options.retries {
+                    return Err(abort_transfer(socket, timed_out_error()));
+                }
+
+                rtt_estimator.back_off();
+                continue;
+            }
+        };
+
+        return match PacketRef::parse_with(&response[..recv_bytes], transfer_options.strictness) {
+            Ok(PacketRef::ACK(packet)) if packet.block == 0 => {
+                // Karn's algorithm: a retransmitted
+                // OACK packet gives no sample
+                if timeouts == 0 {
+                    rtt_estimator.add_sample(sent_at.elapsed());
+                }
+
+                Ok(())
+            }
+            Ok(PacketRef::ERROR(err_packet)) => Err(peer_error(&err_packet)),
+            Err(e) => Err(abort_malformed(socket, e)),
+            _ => Err(abort_transfer(socket, ERRORPacket::IllegalTftpOperation)),
+        };
+    }
+}
+
+/// Sends the content of `reader` to the peer as TFTP DATA packets.
+///
+/// Up to `window_size` blocks are sent before waiting for an ACK packet.
+/// An ACK packet for a block before the end of the window means that
+/// the peer missed the next block, so the transmission rewinds
+/// to the block following the acknowledged one (RFC 7440). An ACK packet
+/// for the block preceding the window means that its first block was
+/// missed, so the window is sent again, once until the peer moves
+/// forward. Other ACK packets for blocks already acknowledged are ignored.
+///
+/// The blocks of the window are sent again each time the timeout expires.
+/// Returns the number of bytes sent.
+pub fn send_file(
+    socket: &TransferSocket,
+    reader: &mut impl Read,
+    transfer_options: &TransferOptions,
+    rtt_estimator: &mut RttEstimator,
+) -> Result<u64, TransferError> {
+    // Blocks sent to the peer and not acknowledged yet
+    let mut window: VecDeque<WindowBlock> = VecDeque::new();
+    let mut sent_bytes: u64 = 0;
+
+    let mut next_block_number = BlockNumber::from(1);
+    let mut reached_end = false;
+
+    // Last block number acknowledged by the peer,
+    // and number of blocks acknowledged so far
+    let mut acked_block_number = BlockNumber::ZERO;
+    let mut acked_block_count: u64 = 0;
+
+    // Number of consecutive timeouts
+    let mut timeouts = 0;
+
+    // Whether the window was already sent again for an
+    // ACK packet of the block preceding it
+    let mut rewound = false;
+
+    // Create a buffer to store the ACK packet, large
+    // enough for an ERROR packet with a long message
+    let mut response = vec![0_u8; transfer_options.block_size + 5];
+
+    loop {
+        while window.len() < transfer_options.window_size && !reached_end {
+            let mut data_buffer = vec![0_u8; transfer_options.block_size];
+
+            let read_bytes = read_block(reader, &mut data_buffer).map_err(TransferError::File)?;
+
+            // The last TFTP DATA packet is
+            // shorter than the block size
+            reached_end = read_bytes < transfer_options.block_size;
+
+            window.push_back(WindowBlock {
+                packet: DATAPacket::build(next_block_number.value(), &data_buffer[..read_bytes])
+                    .map_err(|e| TransferError::Aborted(e.to_string()))?,
+                sent_at: None,
+                retransmitted: false,
+            });
+
+            if !reached_end {
+                next_block_number = match next_block_number.next(transfer_options.rollover) {
+                    Some(block_number) => block_number,
+                    None => return Err(abort_transfer(socket, too_large_error())),
+                };
+            }
+        }
+
+        for block in window.iter_mut() {
+            socket
+                .send(&block.packet.as_bytes())
+                .map_err(TransferError::Socket)?;
+
+            block.retransmitted = block.sent_at.is_some();
+            block.sent_at = Some(Instant::now());
+        }
+
+        // Stale ACK packets do not postpone the retransmission
+        // of the window, which happens once the timeout expires
+        let deadline = Instant::now() + rtt_estimator.rto();
+
+        let acked_blocks = loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+
+            let recv_bytes = match recv_packet(socket, &mut response, timeout)? {
+                Some(bytes) => bytes,
+                None => break None,
+            };
+
+            let ack_block_number =
+                match PacketRef::parse_with(&response[..recv_bytes], transfer_options.strictness) {
+                    Ok(PacketRef::ACK(packet)) => BlockNumber::from(packet.block),
+                    // The OACK packet was sent again before the first
+                    // block was received, it acknowledges no block
+                    Ok(PacketRef::OACK(_)) if acked_block_count == 0 => continue,
+                    Ok(PacketRef::ERROR(err_packet)) => return Err(peer_error(&err_packet)),
+                    Err(e) => return Err(abort_malformed(socket, e)),
+                    _ => return Err(abort_transfer(socket, ERRORPacket::IllegalTftpOperation)),
+                };
+
+            match ack_block_number.offset_from(acked_block_number, transfer_options.rollover) {
+                // The peer acknowledges a block of the window
+                Some(blocks) if blocks > 0 && blocks <= window.len() => break Some(blocks),
+                // The peer missed the first block of the window. Without
+                // a window, this is a duplicate ACK packet of the last block
+                Some(0) if transfer_options.window_size > 1 && !rewound => break Some(0),
+                _ => {}
+            }
+
+            // A duplicate or delayed ACK packet for a block already
+            // acknowledged is ignored: sending the window again each
+            // time would duplicate every following block (Sorcerer's
+            // Apprentice)
+            let is_stale = acked_block_number
+                .offset_from(ack_block_number, transfer_options.rollover)
+                .is_some_and(|blocks| blocks as u64 <= acked_block_count);
+
+            if !is_stale {
+                return Err(abort_transfer(socket, ERRORPacket::IllegalTftpOperation));
+            }
+        };
+
+        let acked_blocks = match acked_blocks {
+            Some(0) => {
+                rewound = true;
+                continue;
+            }
+            Some(blocks) => blocks,
+            None => {
+                timeouts += 1;
+
+                if timeouts > transfer_options.retries {
+                    return Err(abort_transfer(socket, timed_out_error()));
+                }
+
+                rtt_estimator.back_off();
+                continue;
+            }
+        };
+
+        timeouts = 0;
+        rewound = false;
+
+        acked_block_count += acked_blocks as u64;
+
+        for (index, block) in window.drain(..acked_blocks).enumerate() {
+            sent_bytes += block.packet.get_data().len() as u64;
+
+            if index + 1 < acked_blocks {
+                continue;
+            }
+
+            acked_block_number = BlockNumber::from(block.packet.block);
+
+            // Karn's algorithm: only the ACK packet of
+            // a block sent once gives a sample
+            if !block.retransmitted {
+                if let Some(sent_at) = block.sent_at {
+                    rtt_estimator.add_sample(sent_at.elapsed());
+                }
+            }
+        }
+
+        if window.is_empty() && reached_end {
+            return Ok(sent_bytes);
+        }
+    }
+}
+
+/// Receives TFTP DATA packets from the peer and writes them into `writer`.
+///
+/// `initial_ack` is the last packet sent before the first block (ACK
+/// packet for block 0, or OACK packet answering a WRQ), and
+/// `first_data_packet` the first block if it was already received.
+///
+/// An ACK packet is sent for the last block of each window, or as soon as
+/// a block is missing to make the peer rewind (RFC 7440). Blocks already
+/// received are dropped, or acknowledged again without a window. The
+/// transfer is stopped with a `DiskFull` ERROR packet beyond
+/// `max_receive_size` bytes.
+///
+/// The last ACK packet is sent again each time the timeout expires.
+/// The final ACK packet is not sent: the caller stores the file, then
+/// calls [`acknowledge_file`], or sends an ERROR packet if it failed.
+pub fn receive_file(
+    socket: &TransferSocket,
+    writer: &mut impl Write,
+    transfer_options: &TransferOptions,
+    rtt_estimator: &mut RttEstimator,
+    initial_ack: Vec<u8>,
+    first_data_packet: Option<DATAPacket>,
+) -> Result<ReceivedFile, TransferError> {
+    let mut last_block_number = BlockNumber::ZERO;
+    let mut received_blocks: u64 = 0;
+    let mut received_bytes: u64 = 0;
+
+    let mut last_ack = initial_ack;
+
+    // Instant at which the last ACK packet was sent, unless it was
+    // retransmitted since, in which case it gives no sample (Karn)
+    let mut ack_sent_at = match first_data_packet {
+        Some(_) => None,
+        None => Some(Instant::now()),
+    };
+
+    let mut pending_data_packet = first_data_packet;
+
+    // Number of consecutive timeouts
+    let mut timeouts = 0;
+
+    // Number of blocks received since the last ACK packet
+    let mut blocks_in_window = 0;
+
+    // Whether the peer was already asked
+    // to rewind after the last received block
+    let mut rewind_requested = false;
+
+    // Duplicate and out-of-order blocks do not postpone
+    // the retransmission of the last ACK packet
+    let mut deadline = Instant::now() + rtt_estimator.rto();
+
+    // Create a buffer to store a TFTP DATA packet (block size + 4
+    // bytes), plus one byte so that an oversized one is not truncated
+    let mut data_buffer = vec![0_u8; transfer_options.block_size + 5];
+
+    loop {
+        let pending = pending_data_packet.take();
+
+        let data_packet = match pending.as_ref() {
+            Some(packet) => DATAPacketRef::from(packet),
+            None => {
+                let read_bytes = match recv_packet(
+                    socket,
+                    &mut data_buffer,
+                    deadline.saturating_duration_since(Instant::now()),
+                )? {
+                    Some(bytes) => bytes,
+                    None => {
+                        timeouts += 1;
+
+                        if timeouts > transfer_options.retries {
+                            return Err(abort_transfer(socket, timed_out_error()));
+                        }
+
+                        socket.send(&last_ack).map_err(TransferError::Socket)?;
+
+                        rtt_estimator.back_off();
+                        ack_sent_at = None;
+                        deadline = Instant::now() + rtt_estimator.rto();
+                        continue;
+                    }
+                };
+
+                match PacketRef::parse_with(&data_buffer[..read_bytes], transfer_options.strictness)
+                {
+                    Ok(PacketRef::DATA(packet)) => packet,
+                    // The ACK packet for the OACK packet was lost
+                    Ok(PacketRef::OACK(_)) if received_blocks == 0 => {
+                        socket.send(&last_ack).map_err(TransferError::Socket)?;
+
+                        ack_sent_at = None;
+                        continue;
+                    }
+                    Ok(PacketRef::ERROR(err_packet)) => return Err(peer_error(&err_packet)),
+                    Err(e) => return Err(abort_malformed(socket, e)),
+                    _ => return Err(abort_transfer(socket, ERRORPacket::IllegalTftpOperation)),
+                }
+            }
+        };
+
+        if data_packet.get_data().len() > transfer_options.block_size {
+            return Err(abort_transfer(socket, ERRORPacket::IllegalTftpOperation));
+        }
+
+        let block_number = BlockNumber::from(data_packet.block);
+        let offset = block_number.offset_from(last_block_number, transfer_options.rollover);
+
+        // The last block was sent again because its ACK packet was lost,
+        // so the block is acknowledged again. With a window, such an ACK
+        // packet would rewind the peer and the last ACK packet is
+        // only sent again once the timeout expires
+        if offset == Some(0) && received_blocks > 0 && transfer_options.window_size == 1 {
+            last_ack = ACKPacket {
+                block: last_block_number.value(),
+            }
+            .as_bytes();
+
+            socket.send(&last_ack).map_err(TransferError::Socket)?;
+
+            ack_sent_at = None;
+            blocks_in_window = 0;
+            continue;
+        }
+
+        if offset != Some(1) {
+            // A block of the current window is missing
+            let is_in_window =
+                offset.is_some_and(|blocks| blocks > 1 && blocks <= transfer_options.window_size);
+
+            // Duplicates of blocks already received are dropped
+            let is_stale = last_block_number
+                .offset_from(block_number, transfer_options.rollover)
+                .is_some_and(|blocks| (blocks as u64) < received_blocks);
+
+            if is_stale {
+                continue;
+            }
+
+            if !is_in_window {
+                return Err(abort_transfer(socket, ERRORPacket::IllegalTftpOperation));
+            }
+
+            // Acknowledge the last block received in order
+            // only once, the following blocks are dropped
+            if !rewind_requested {
+                last_ack = ACKPacket {
+                    block: last_block_number.value(),
+                }
+                .as_bytes();
+
+                socket.send(&last_ack).map_err(TransferError::Socket)?;
+
+                // The rewound blocks are retransmissions
+                ack_sent_at = None;
+                blocks_in_window = 0;
+                rewind_requested = true;
+            }
+
+            continue;
+        }
+
+        if let Some(sent_at) = ack_sent_at.take() {
+            rtt_estimator.add_sample(sent_at.elapsed());
+        }
+
+        timeouts = 0;
+        deadline = Instant::now() + rtt_estimator.rto();
+
+        received_blocks += 1;
+        received_bytes += data_packet.get_data().len() as u64;
+
+        // Peers that do not announce the size
+        // of the file are stopped at the quota
+        if transfer_options
+            .max_receive_size
+            .is_some_and(|max_receive_size| received_bytes > max_receive_size)
+        {
+            return Err(abort_transfer(socket, ERRORPacket::DiskFull));
+        }
+
+        writer
+            .write_all(data_packet.get_data())
+            .map_err(TransferError::File)?;
+
+        last_block_number = block_number;
+        blocks_in_window += 1;
+        rewind_requested = false;
+
+        // The last TFTP DATA packet is
+        // shorter than the block size
+        let is_last_block = data_packet.get_data().len() < transfer_options.block_size;
+
+        // The next block has no block number
+        // when the rollover is disabled
+        if !is_last_block && last_block_number.next(transfer_options.rollover).is_none() {
+            return Err(abort_transfer(socket, too_large_error()));
+        }
+
+        if is_last_block {
+            return Ok(ReceivedFile {
+                bytes: received_bytes,
+                last_block_number,
+            });
+        }
+
+        if blocks_in_window == transfer_options.window_size {
+            last_ack = ACKPacket {
+                block: last_block_number.value(),
+            }
+            .as_bytes();
+
+            socket.send(&last_ack).map_err(TransferError::Socket)?;
+
+            ack_sent_at = Some(Instant::now());
+            blocks_in_window = 0;
+        }
+    }
+}
+
+/// Sends the final ACK packet of a file received by [`receive_file`],
+/// once the caller stored it, then dallies (see [`dally`]).
+pub fn acknowledge_file(
+    socket: &TransferSocket,
+    received_file: &ReceivedFile,
+    transfer_options: &TransferOptions,
+    rtt_estimator: &RttEstimator,
+) {
+    // A lost final ACK packet is sent again while dallying
+    let _ = socket.send(
+        &ACKPacket {
+            block: received_file.last_block_number.value(),
+        }
+        .as_bytes(),
+    );
+
+    dally(socket, transfer_options, rtt_estimator);
+}
+
+/// Waits for the peer to send the last TFTP DATA packet again, in case
+/// the final ACK packet was lost, and acknowledges it again (RFC 1350).
+/// The other blocks of the last window are ignored.
+///
+/// The transfer is complete once no packet arrives within the timeout.
+fn dally(
+    socket: &TransferSocket,
+    transfer_options: &TransferOptions,
+    rtt_estimator: &RttEstimator,
+) {
+    let mut data_buffer = vec![0_u8; transfer_options.block_size + 5];
+    let mut acks = 0;
+
+    while acks < transfer_options.retries {
+        let bytes = match recv_packet(socket, &mut data_buffer, rtt_estimator.rto()) {
+            Ok(Some(bytes)) => bytes,
+            _ => return,
+        };
+
+        match PacketRef::parse_with(&data_buffer[..bytes], transfer_options.strictness) {
+            // Only the last TFTP DATA packet is
+            // shorter than the block size
+            Ok(PacketRef::DATA(packet))
+                if packet.get_data().len() < transfer_options.block_size =>
+            {
+                let _ = socket.send(
+                    &ACKPacket {
+                        block: packet.block,
+                    }
+                    .as_bytes(),
+                );
+                acks += 1;
+            }
+            Ok(PacketRef::DATA(_)) => {}
+            _ => return,
+        }
+    }
+}
+
+/// Receives a packet from the peer into `buffer`, returning
+/// `None` when no packet arrived before the `timeout`.
+fn recv_packet(
+    socket: &TransferSocket,
+    buffer: &mut [u8],
+    timeout: Duration,
+) -> Result<Option<usize>, TransferError> {
+    socket.recv(buffer, timeout).map_err(TransferError::Socket)
+}
+
+/// A file received by [`receive_file`],
+/// waiting for its final ACK packet.
+#[derive(Debug)]
+pub struct ReceivedFile {
+    /// Number of bytes received.
+    pub bytes: u64,
+
+    /// Block number of the last TFTP DATA packet.
+    last_block_number: BlockNumber,
+}
+
+/// A TFTP DATA packet of the window, waiting for its ACK packet.
+struct WindowBlock {
+    packet: DATAPacket,
+
+    /// Instant of the last transmission, if any.
+    sent_at: Option<Instant>,
+
+    /// Whether the block was sent more than once.
+    retransmitted: bool,
+}
+
+/// ERROR packet sent to the peer when the
+/// retransmissions of a packet are exhausted.
+fn timed_out_error() -> ERRORPacket {
+    ERRORPacket::NotDefined("Transfer timed out".to_string())
+}
+
+/// ERROR packet sent to the peer when a file needs more
+/// block numbers than available without rollover.
+pub fn too_large_error() -> ERRORPacket {
+    ERRORPacket::NotDefined("File too large: block number rollover is disabled".to_string())
+}
+
+/// Sends an ERROR packet to the peer and
+/// returns its message to report the abort.
+fn abort_transfer(socket: &TransferSocket, err_packet: ERRORPacket) -> TransferError {
+    let _ = socket.send(&err_packet.as_bytes());
+    TransferError::Aborted(err_packet.get_error_message())
+}
+
+/// Reports an ERROR packet by which
+/// the peer aborts the transfer.
+fn peer_error(err_packet: &ERRORPacketRef) -> TransferError {
+    TransferError::Peer {
+        code: err_packet.get_error_code(),
+        message: err_packet.get_error_message().to_string(),
+    }
+}
+
+/// Aborts the transfer on a malformed packet from the
+/// peer, reporting what is wrong with the packet.
+fn abort_malformed(socket: &TransferSocket, packet_error: PacketError) -> TransferError {
+    let _ = socket.send(&ERRORPacket::IllegalTftpOperation.as_bytes());
+    TransferError::Aborted(format!(
+        "{} ({})",
+        ERRORPacket::IllegalTftpOperation.get_error_message(),
+        packet_error
+    ))
+}
+
+/// Reads from `reader` until `buffer` is full or the end of
+/// the data is reached, returning the number of bytes read.
+///
+/// Only the last TFTP DATA packet may be shorter than the block size,
+/// so a short read must not be mistaken for the end of the transfer.
+fn read_block(reader: &mut impl Read, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut read_bytes = 0;
+
+    while read_bytes < buffer.len() {
+        match reader.read(&mut buffer[read_bytes..]) {
+            Ok(0) => break,
+            Ok(bytes) => read_bytes += bytes,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(read_bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::UdpSocket,
+        thread::{self, JoinHandle},
+    };
+
+    use super::*;
+
+    const BLOCK_SIZE: usize = 8;
+
+    /// Retransmission timeout of the tests, long enough for a
+    /// rewind to be told apart from a retransmission.
+    const TIMEOUT: Duration = Duration::from_millis(500);
+
+    fn transfer_options(window_size: usize) -> TransferOptions {
+        TransferOptions {
+            block_size: BLOCK_SIZE,
+            timeout: TIMEOUT,
+            adaptive_timeout: false,
+            retries: 3,
+            window_size,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the socket of the transfer under test
+    /// and the socket of the peer, driven by the test.
+    fn socket_pair() -> (TransferSocket, UdpSocket) {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let peer = UdpSocket::bind("127.0.0.1:0").unwrap();
+
+        peer.set_read_timeout(Some(TIMEOUT * 4)).unwrap();
+        peer.connect(socket.local_addr().unwrap()).unwrap();
+
+        let socket = TransferSocket::new(socket, peer.local_addr().unwrap(), |_| {});
+
+        (socket, peer)
+    }
+
+    /// Data of `blocks` full blocks followed by a short one.
+    fn file_data(blocks: usize) -> Vec<u8> {
+        (0..blocks * BLOCK_SIZE + 3).map(|i| i as u8).collect()
+    }
+
+    fn spawn_send_file(
+        socket: TransferSocket,
+        data: Vec<u8>,
+        transfer_options: TransferOptions,
+    ) -> JoinHandle<Result<u64, TransferError>> {
+        thread::spawn(move || {
+            let mut rtt_estimator = RttEstimator::new(&transfer_options);
+            send_file(
+                &socket,
+                &mut data.as_slice(),
+                &transfer_options,
+                &mut rtt_estimator,
+            )
+        })
+    }
+
+    /// Receives a file, then acknowledges it once
+    /// received, returning the bytes written.
+    fn spawn_receive_file(
+        socket: TransferSocket,
+        transfer_options: TransferOptions,
+    ) -> JoinHandle<Result<Vec<u8>, TransferError>> {
+        thread::spawn(move || {
+            let mut rtt_estimator = RttEstimator::new(&transfer_options);
+            let mut data = Vec::new();
+
+            let received_file = receive_file(
+                &socket,
+                &mut data,
+                &transfer_options,
+                &mut rtt_estimator,
+                ACKPacket { block: 0 }.as_bytes(),
+                None,
+            )?;
+
+            assert_eq!(received_file.bytes, data.len() as u64);
+
+            acknowledge_file(&socket, &received_file, &transfer_options, &rtt_estimator);
+            Ok(data)
+        })
+    }
+
+    fn recv(peer: &UdpSocket) -> Vec<u8> {
+        let mut buffer = [0_u8; 1024];
+        let bytes = peer.recv(&mut buffer).expect("no packet received");
+        buffer[..bytes].to_vec()
+    }
+
+    fn recv_data(peer: &UdpSocket) -> (u16, Vec<u8>) {
+        match PacketRef::parse(&recv(peer)) {
+            Ok(PacketRef::DATA(packet)) => (packet.block, packet.get_data().to_vec()),
+            packet => panic!("expected a DATA packet, received {:?}", packet),
+        }
+    }
+
+    fn recv_ack(peer: &UdpSocket) -> u16 {
+        match PacketRef::parse(&recv(peer)) {
+            Ok(PacketRef::ACK(packet)) => packet.block,
+            packet => panic!("expected an ACK packet, received {:?}", packet),
+        }
+    }
+
+    /// Checks that no packet arrives before the
+    /// retransmission timeout expires.
+    fn assert_silent(peer: &UdpSocket) {
+        peer.set_read_timeout(Some(TIMEOUT / 2)).unwrap();
+        let mut buffer = [0_u8; 1024];
+        let received = peer.recv(&mut buffer);
+        peer.set_read_timeout(Some(TIMEOUT * 4)).unwrap();
+
+        assert!(received.is_err(), "unexpected packet {:?}", received);
+    }
+
+    fn send_ack(peer: &UdpSocket, block: u16) {
+        peer.send(&ACKPacket { block }.as_bytes()).unwrap();
+    }
+
+    /// Sends the block `block` of `data`, the
+    /// first block being numbered 1.
+    fn send_data(peer: &UdpSocket, data: &[u8], block: u16) {
+        let start = (block as usize - 1) * BLOCK_SIZE;
+        let end = (start + BLOCK_SIZE).min(data.len());
+
+        peer.send(
+            &DATAPacket::build(block, &data[start..end])
+                .unwrap()
+                .as_bytes(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn send_file_rewinds_after_a_missing_block() {
+        let (socket, peer) = socket_pair();
+        let data = file_data(4);
+        let sender = spawn_send_file(socket, data.clone(), transfer_options(4));
+
+        for block in 1..=4 {
+            assert_eq!(recv_data(&peer).0, block);
+        }
+
+        // Block 3 is missing: the window starts again after block 2
+        send_ack(&peer, 2);
+
+        let mut received = data[..2 * BLOCK_SIZE].to_vec();
+
+        for block in 3..=5 {
+            let (block_number, block_data) = recv_data(&peer);
+            assert_eq!(block_number, block);
+            received.extend(block_data);
+        }
+
+        send_ack(&peer, 5);
+
+        assert_eq!(sender.join().unwrap().unwrap(), data.len() as u64);
+        assert_eq!(received, data);
+    }
+
+    #[test]
+    fn send_file_rewinds_once_on_an_ack_preceding_the_window() {
+        let (socket, peer) = socket_pair();
+        let data = file_data(8);
+        let sender = spawn_send_file(socket, data.clone(), transfer_options(4));
+
+        for block in 1..=4 {
+            assert_eq!(recv_data(&peer).0, block);
+        }
+
+        send_ack(&peer, 4);
+
+        for block in 5..=8 {
+            assert_eq!(recv_data(&peer).0, block);
+        }
+
+        // Block 5 is missing (RFC 7440): the ACK packet of block 4
+        // rewinds the window before the timeout expires
+        send_ack(&peer, 4);
+
+        for block in 5..=8 {
+            assert_eq!(recv_data(&peer).0, block);
+        }
+
+        // A duplicate of the same ACK packet does not
+        send_ack(&peer, 4);
+        assert_silent(&peer);
+
+        // The window is still retransmitted once the timeout expires
+        for block in 5..=8 {
+            assert_eq!(recv_data(&peer).0, block);
+        }
+
+        send_ack(&peer, 8);
+        assert_eq!(recv_data(&peer), (9, data[8 * BLOCK_SIZE..].to_vec()));
+        send_ack(&peer, 9);
+
+        assert_eq!(sender.join().unwrap().unwrap(), data.len() as u64);
+    }
+
+    #[test]
+    fn send_file_ignores_duplicate_acks() {
+        let (socket, peer) = socket_pair();
+        let data = file_data(2);
+        let sender = spawn_send_file(socket, data.clone(), transfer_options(1));
+
+        assert_eq!(recv_data(&peer).0, 1);
+        send_ack(&peer, 1);
+        assert_eq!(recv_data(&peer).0, 2);
+
+        // Answering the delayed duplicate would send every
+        // following block twice (Sorcerer's Apprentice)
+        send_ack(&peer, 1);
+        assert_silent(&peer);
+
+        // Block 2 is only sent again once the timeout expires
+        assert_eq!(recv_data(&peer).0, 2);
+        send_ack(&peer, 2);
+        assert_eq!(recv_data(&peer).0, 3);
+        send_ack(&peer, 3);
+
+        assert_eq!(sender.join().unwrap().unwrap(), data.len() as u64);
+    }
+
+    #[test]
+    fn send_file_ignores_stale_acks_of_earlier_windows() {
+        let (socket, peer) = socket_pair();
+        let data = file_data(8);
+        let sender = spawn_send_file(socket, data.clone(), transfer_options(4));
+
+        for block in 1..=4 {
+            assert_eq!(recv_data(&peer).0, block);
+        }
+
+        send_ack(&peer, 4);
+
+        for block in 5..=8 {
+            assert_eq!(recv_data(&peer).0, block);
+        }
+
+        // The ACK packet of a block of the previous window
+        send_ack(&peer, 2);
+        assert_silent(&peer);
+
+        send_ack(&peer, 8);
+        assert_eq!(recv_data(&peer).0, 9);
+        send_ack(&peer, 9);
+
+        assert_eq!(sender.join().unwrap().unwrap(), data.len() as u64);
+    }
+
+    #[test]
+    fn send_file_aborts_on_an_ack_beyond_the_window() {
+        let (socket, peer) = socket_pair();
+        let sender = spawn_send_file(socket, file_data(8), transfer_options(4));
+
+        for block in 1..=4 {
+            assert_eq!(recv_data(&peer).0, block);
+        }
+
+        send_ack(&peer, 6);
+
+        match PacketRef::parse(&recv(&peer)) {
+            Ok(PacketRef::ERROR(err_packet)) => assert_eq!(err_packet.get_error_code(), 4),
+            packet => panic!("expected an ERROR packet, received {:?}", packet),
+        }
+
+        assert!(matches!(
+            sender.join().unwrap(),
+            Err(TransferError::Aborted(_))
+        ));
+    }
+
+    #[test]
+    fn send_file_rolls_over_block_numbers() {
+        let (socket, peer) = socket_pair();
+        let data = file_data(BlockNumber::MAX_BLOCKS as usize + 10);
+        let window_size = 64;
+        let sender = spawn_send_file(socket, data.clone(), transfer_options(window_size));
+
+        let mut received = Vec::with_capacity(data.len());
+        let mut expected_block = BlockNumber::from(1);
+
+        loop {
+            let mut last_block = None;
+
+            for _ in 0..window_size {
+                let (block_number, block_data) = recv_data(&peer);
+                assert_eq!(block_number, expected_block.value());
+
+                let is_last_block = block_data.len() < BLOCK_SIZE;
+                received.extend(block_data);
+                last_block = Some(block_number);
+
+                if is_last_block {
+                    break;
+                }
+
+                expected_block = expected_block.next(BlockRollover::ToZero).unwrap();
+            }
+
+            send_ack(&peer, last_block.unwrap());
+
+            if received.len() == data.len() {
+                break;
+            }
+        }
+
+        assert_eq!(sender.join().unwrap().unwrap(), data.len() as u64);
+        assert_eq!(received, data);
+    }
+
+    #[test]
+    fn receive_file_asks_once_for_a_rewind() {
+        let (socket, peer) = socket_pair();
+        let data = file_data(5);
+        let receiver = spawn_receive_file(socket, transfer_options(4));
+
+        assert_eq!(recv_ack(&peer), 0);
+
+        // Block 3 is lost: only the first block out
+        // of order is answered, the others are dropped
+        send_data(&peer, &data, 1);
+        send_data(&peer, &data, 2);
+        send_data(&peer, &data, 4);
+        send_data(&peer, &data, 5);
+
+        assert_eq!(recv_ack(&peer), 2);
+        assert_silent(&peer);
+
+        for block in 3..=6 {
+            send_data(&peer, &data, block);
+        }
+
+        assert_eq!(recv_ack(&peer), 6);
+        assert_eq!(receiver.join().unwrap().unwrap(), data);
+    }
+
+    #[test]
+    fn receive_file_acknowledges_a_duplicate_block_again() {
+        let (socket, peer) = socket_pair();
+        let data = file_data(2);
+        let receiver = spawn_receive_file(socket, transfer_options(1));
+
+        assert_eq!(recv_ack(&peer), 0);
+
+        send_data(&peer, &data, 1);
+        assert_eq!(recv_ack(&peer), 1);
+
+        // The ACK packet of block 1 was lost
+        send_data(&peer, &data, 1);
+        assert_eq!(recv_ack(&peer), 1);
+
+        for block in 2..=3 {
+            send_data(&peer, &data, block);
+            assert_eq!(recv_ack(&peer), block);
+        }
+
+        assert_eq!(receiver.join().unwrap().unwrap(), data);
+    }
+
+    #[test]
+    fn receive_file_drops_duplicate_blocks_of_a_window() {
+        let (socket, peer) = socket_pair();
+        let data = file_data(4);
+        let receiver = spawn_receive_file(socket, transfer_options(2));
+
+        assert_eq!(recv_ack(&peer), 0);
+
+        send_data(&peer, &data, 1);
+        send_data(&peer, &data, 2);
+        assert_eq!(recv_ack(&peer), 2);
+
+        // Acknowledging the duplicates would rewind the peer
+        send_data(&peer, &data, 1);
+        send_data(&peer, &data, 2);
+        assert_silent(&peer);
+
+        // The last ACK packet is sent again once the timeout expires
+        assert_eq!(recv_ack(&peer), 2);
+
+        for block in 3..=5 {
+            send_data(&peer, &data, block);
+        }
+
+        assert_eq!(recv_ack(&peer), 4);
+        assert_eq!(recv_ack(&peer), 5);
+        assert_eq!(receiver.join().unwrap().unwrap(), data);
+    }
+
+    #[test]
+    fn receive_file_rolls_over_block_numbers() {
+        let (socket, peer) = socket_pair();
+        let data = file_data(BlockNumber::MAX_BLOCKS as usize + 10);
+        let window_size = 64;
+
+        let mut transfer_options = transfer_options(window_size);
+        transfer_options.rollover = BlockRollover::ToOne;
+
+        let receiver = spawn_receive_file(socket, transfer_options);
+
+        assert_eq!(recv_ack(&peer), 0);
+
+        let mut block_number = BlockNumber::ZERO;
+
+        for chunk in data.chunks(BLOCK_SIZE * window_size) {
+            for block_data in chunk.chunks(BLOCK_SIZE) {
+                block_number = block_number.next(BlockRollover::ToOne).unwrap();
+
+                peer.send(
+                    &DATAPacket::build(block_number.value(), block_data)
+                        .unwrap()
+                        .as_bytes(),
+                )
+                .unwrap();
+            }
+
+            assert_eq!(recv_ack(&peer), block_number.value());
+        }
+
+        assert_eq!(receiver.join().unwrap().unwrap(), data);
+    }
+
+    #[test]
+    fn receive_file_stops_at_the_quota() {
+        let (socket, peer) = socket_pair();
+        let data = file_data(3);
+
+        let mut transfer_options = transfer_options(1);
+        transfer_options.max_receive_size = Some(2 * BLOCK_SIZE as u64);
+
+        let receiver = spawn_receive_file(socket, transfer_options);
+
+        assert_eq!(recv_ack(&peer), 0);
+
+        for block in 1..=2 {
+            send_data(&peer, &data, block);
+            assert_eq!(recv_ack(&peer), block);
+        }
+
+        send_data(&peer, &data, 3);
+
+        match PacketRef::parse(&recv(&peer)) {
+            Ok(PacketRef::ERROR(err_packet)) => assert_eq!(err_packet.get_error_code(), 3),
+            packet => panic!("expected an ERROR packet, received {:?}", packet),
+        }
+
+        assert!(matches!(
+            receiver.join().unwrap(),
+            Err(TransferError::Aborted(_))
+        ));
+    }
+
+    #[test]
+    fn dally_acknowledges_the_last_block_again() {
+        let (socket, peer) = socket_pair();
+        let data = file_data(1);
+        let receiver = spawn_receive_file(socket, transfer_options(2));
+
+        assert_eq!(recv_ack(&peer), 0);
+
+        send_data(&peer, &data, 1);
+        send_data(&peer, &data, 2);
+        assert_eq!(recv_ack(&peer), 2);
+
+        // The final ACK packet was lost: the last window is sent
+        // again and only its last block is acknowledged
+        send_data(&peer, &data, 1);
+        send_data(&peer, &data, 2);
+        assert_eq!(recv_ack(&peer), 2);
+        assert_silent(&peer);
+
+        assert_eq!(receiver.join().unwrap().unwrap(), data);
+    }
+}
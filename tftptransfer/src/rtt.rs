@@ -0,0 +1,85 @@
+use std::time::Duration;
+
+use crate::TransferOptions;
+
+/// Estimates the round-trip time of a session to compute
+/// its retransmission timeout (RFC 6298).
+///
+/// Samples must only be taken from packets sent once (Karn's algorithm),
+/// since the ACK of a retransmitted packet cannot be matched with
+/// the transmission it answers.
+#[derive(Debug)]
+pub struct RttEstimator {
+    /// Smoothed round-trip time.
+    srtt: Option<Duration>,
+
+    /// Round-trip time variation.
+    rttvar: Duration,
+
+    /// Current retransmission timeout.
+    rto: Duration,
+
+    /// Whether the retransmission timeout follows the estimation,
+    /// otherwise it is the timeout negotiated with the peer.
+    adaptive: bool,
+}
+
+impl RttEstimator {
+    /// Smallest retransmission timeout, so that
+    /// a fast LAN does not cause spurious retransmissions.
+    pub const MIN_RTO: Duration = Duration::from_millis(100);
+
+    /// Largest retransmission timeout reached by the exponential backoff.
+    pub const MAX_RTO: Duration = Duration::from_secs(60);
+
+    /// Constructs a new `RttEstimator` starting from the timeout of the transfer.
+    pub fn new(transfer_options: &TransferOptions) -> Self {
+        Self {
+            srtt: None,
+            rttvar: Duration::ZERO,
+            rto: transfer_options.timeout,
+            adaptive: transfer_options.adaptive_timeout,
+        }
+    }
+
+    /// Get the current retransmission timeout.
+    pub fn rto(&self) -> Duration {
+        self.rto
+    }
+
+    /// Get the smoothed round-trip time, if any sample was taken.
+    pub fn smoothed_rtt(&self) -> Option<Duration> {
+        self.srtt
+    }
+
+    /// Updates the estimation with a round-trip time measured
+    /// on a packet that was not retransmitted.
+    pub fn add_sample(&mut self, rtt: Duration) {
+        let srtt = match self.srtt {
+            None => {
+                self.rttvar = rtt / 2;
+                rtt
+            }
+            Some(srtt) => {
+                // RTTVAR <- 3/4 * RTTVAR + 1/4 * |SRTT - R'|
+                self.rttvar = self.rttvar * 3 / 4 + srtt.abs_diff(rtt) / 4;
+
+                // SRTT <- 7/8 * SRTT + 1/8 * R'
+                srtt * 7 / 8 + rtt / 8
+            }
+        };
+
+        self.srtt = Some(srtt);
+
+        if self.adaptive {
+            self.rto = (srtt + self.rttvar * 4).clamp(Self::MIN_RTO, Self::MAX_RTO);
+        }
+    }
+
+    /// Doubles the retransmission timeout after it expired.
+    pub fn back_off(&mut self) {
+        if self.adaptive {
+            self.rto = (self.rto * 2).min(Self::MAX_RTO);
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use std::{
+    io,
+    net::{SocketAddr, UdpSocket},
+    time::{Duration, Instant},
+};
+
+use tftppacket::ERRORPacket;
+
+/// UDP socket of a transfer, exchanging packets
+/// with the transfer ID (TID) of the peer.
+///
+/// The socket is not connected, so that packets from any other
+/// source are seen and answered with an ERROR packet (code 5)
+/// without terminating the transfer (RFC 1350).
+#[derive(Debug)]
+pub struct TransferSocket {
+    socket: UdpSocket,
+    peer_addr: SocketAddr,
+
+    /// Called with the source of each rejected packet,
+    /// so that the caller reports it.
+    on_unknown_tid: fn(SocketAddr),
+}
+
+impl TransferSocket {
+    /// Constructs a new `TransferSocket` for the peer at `peer_addr`.
+    pub fn new(socket: UdpSocket, peer_addr: SocketAddr, on_unknown_tid: fn(SocketAddr)) -> Self {
+        Self {
+            socket,
+            peer_addr,
+            on_unknown_tid,
+        }
+    }
+
+    /// Sends a packet to the peer.
+    pub fn send(&self, buffer: &[u8]) -> io::Result<usize> {
+        self.socket.send_to(buffer, self.peer_addr)
+    }
+
+    /// Receives a packet from the peer into `buffer`, returning
+    /// `None` when no packet arrived before the `timeout`.
+    ///
+    /// Packets from other sources do not postpone the timeout.
+    pub fn recv(&self, buffer: &mut [u8], timeout: Duration) -> io::Result<Option<usize>> {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+
+            // The socket does not accept a zero timeout
+            if timeout.is_zero() {
+                return Ok(None);
+            }
+
+            self.socket.set_read_timeout(Some(timeout))?;
+
+            match self.socket.recv_from(buffer) {
+                Ok((bytes, source)) if source == self.peer_addr => return Ok(Some(bytes)),
+                Ok((_, source)) => {
+                    reject_unknown_tid(&self.socket, source);
+                    (self.on_unknown_tid)(source);
+                }
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    ) =>
+                {
+                    return Ok(None)
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// Answers a packet received from an unexpected
+/// source with an ERROR packet (code 5).
+pub fn reject_unknown_tid(socket: &UdpSocket, source: SocketAddr) {
+    let _ = socket.send_to(&ERRORPacket::UknownTransferID.as_bytes(), source);
+}